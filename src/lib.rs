@@ -3,6 +3,15 @@
 //! # libpbm
 //!
 //! utilities for generating netpbm images.
+//!
+//! the in-memory image types only need `alloc`. file IO, and anything that needs floating point
+//! math, is behind the `std` feature, which is enabled by default.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{format, string::String, vec, vec::Vec};
 
 /// NetPBMSaver
 ///
@@ -12,14 +21,346 @@ pub trait NetPBMSaver {
     fn to_ascii(&self, comment: Option<&str>) -> String;
     /// create a binary representation of the image file.
     fn to_raw(&self) -> Vec<u8>;
+    /// create a binary representation of the image file, with a choice of 16-bit sample order.
+    fn to_raw_with(&self, _byte_order: ByteOrder) -> Vec<u8> {
+        self.to_raw()
+    }
+    /// append the binary representation of the image file to a buffer.
+    fn write_raw_into(&self, buf: &mut Vec<u8>) {
+        self.write_raw_into_with(buf, ByteOrder::BigEndian);
+    }
+    /// append the binary representation of the image file to a buffer, with a choice of 16-bit sample order.
+    fn write_raw_into_with(&self, buf: &mut Vec<u8>, byte_order: ByteOrder) {
+        buf.extend_from_slice(&self.to_raw_with(byte_order));
+    }
+    /// the exact length of the binary representation.
+    fn raw_size(&self) -> usize {
+        self.to_raw().len()
+    }
+    /// an upper bound on the length of the text representation, without a comment.
+    fn ascii_size_estimate(&self) -> usize {
+        self.to_ascii(None).len()
+    }
+}
+
+/// errors produced while working with netpbm images.
+#[derive(Debug)]
+pub enum PbmError {
+    /// a rectangle does not fit inside the image.
+    OutOfBounds {
+        /// x position of the rectangle.
+        x: usize,
+        /// y position of the rectangle.
+        y: usize,
+        /// width of the rectangle.
+        width: usize,
+        /// height of the rectangle.
+        height: usize,
+    },
+    /// an image does not have the expected size.
+    DimensionMismatch {
+        /// expected width and height.
+        expected: (usize, usize),
+        /// actual width and height.
+        found: (usize, usize),
+    },
+    /// an image does not have the expected maximum value.
+    MaxValMismatch {
+        /// expected maximum value.
+        expected: u16,
+        /// actual maximum value.
+        found: u16,
+    },
+    /// a row of tiles does not have the same number of tiles as the first row.
+    TileCountMismatch {
+        /// index of the row of tiles.
+        row: usize,
+        /// number of tiles in the first row.
+        expected: usize,
+        /// number of tiles in this row.
+        found: usize,
+    },
+    /// no tiles were given.
+    NoTiles,
+    /// a scale factor of 0 was given.
+    ZeroFactor,
+    /// a row of pixels does not have as many pixels as the image is wide.
+    RowLengthMismatch {
+        /// index of the row.
+        row: usize,
+        /// width of the image.
+        expected: usize,
+        /// number of pixels in the row.
+        found: usize,
+    },
+    /// the header of a file could not be parsed.
+    InvalidHeader {
+        /// position in the file where parsing failed.
+        offset: usize,
+    },
+    /// a pixel of a file could not be parsed.
+    InvalidSample {
+        /// position in the file of the sample.
+        offset: usize,
+        /// index of the pixel, in row-major order.
+        pixel: usize,
+    },
+    /// a file ended before all of its pixels were read.
+    UnexpectedEof,
+    /// a sample is larger than the maximum value of the image.
+    SampleOutOfRange {
+        /// the sample.
+        value: u16,
+        /// maximum value of the image.
+        max_val: u16,
+    },
+    /// more pixels were written than fit in the image.
+    TooManyPixels,
+    /// a pixel does not have the number of samples the image needs.
+    DepthMismatch {
+        /// samples per pixel in the image.
+        expected: usize,
+        /// samples in the pixel.
+        found: usize,
+    },
+    /// a NetPAM image does not have the expected tuple type.
+    TupleTypeMismatch {
+        /// tuple type the operation needs.
+        expected: TupleType,
+        /// tuple type of the image.
+        found: TupleType,
+    },
+    /// a blur was given a sigma that isn't positive.
+    InvalidSigma {
+        /// the sigma.
+        sigma: f64,
+    },
+    /// a gamma that isn't a positive number was given.
+    InvalidGamma {
+        /// the gamma.
+        gamma: f64,
+    },
+    /// luma coefficients were negative or not a number.
+    InvalidCoefficients {
+        /// the coefficients.
+        coeffs: [f64; 3],
+    },
+    /// a file could not be read.
+    #[cfg(feature = "std")]
+    Io {
+        /// the kind of error reading produced.
+        kind: std::io::ErrorKind,
+    },
+}
+
+impl core::fmt::Display for PbmError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PbmError::OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "rectangle {}x{} at ({}, {}) is outside the image",
+                width, height, x, y
+            ),
+            PbmError::DimensionMismatch { expected, found } => write!(
+                f,
+                "expected an image of size {}x{}, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            PbmError::MaxValMismatch { expected, found } => write!(
+                f,
+                "expected a maximum value of {}, found {}",
+                expected, found
+            ),
+            PbmError::TileCountMismatch {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} tiles, but the first row has {}",
+                row, found, expected
+            ),
+            PbmError::NoTiles => write!(f, "no tiles were given"),
+            PbmError::ZeroFactor => write!(f, "the factor must be larger than 0"),
+            PbmError::RowLengthMismatch {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} pixels, but the image is {} wide",
+                row, found, expected
+            ),
+            PbmError::InvalidHeader { offset } => write!(f, "invalid header at byte {}", offset),
+            PbmError::InvalidSample { offset, pixel } => {
+                write!(f, "invalid sample for pixel {} at byte {}", pixel, offset)
+            }
+            PbmError::UnexpectedEof => write!(f, "unexpected end of file"),
+            PbmError::SampleOutOfRange { value, max_val } => write!(
+                f,
+                "sample {} is larger than the maximum value {}",
+                value, max_val
+            ),
+            PbmError::TooManyPixels => write!(f, "more pixels were written than fit in the image"),
+            PbmError::DepthMismatch { expected, found } => write!(
+                f,
+                "expected {} samples per pixel, found {}",
+                expected, found
+            ),
+            PbmError::TupleTypeMismatch { expected, found } => write!(
+                f,
+                "expected a {} image, found {}",
+                expected.get_tuple_type(),
+                found.get_tuple_type()
+            ),
+            PbmError::InvalidSigma { sigma } => {
+                write!(f, "sigma must be larger than 0, found {}", sigma)
+            }
+            PbmError::InvalidGamma { gamma } => {
+                write!(f, "gamma must be larger than 0, found {}", gamma)
+            }
+            PbmError::InvalidCoefficients { coeffs } => write!(
+                f,
+                "luma coefficients must not be negative, found {:?}",
+                coeffs
+            ),
+            #[cfg(feature = "std")]
+            PbmError::Io { kind } => write!(f, "could not read file: {}", kind),
+        }
+    }
+}
+
+impl core::error::Error for PbmError {}
+
+/// the magic numbers of the netpbm formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// ASCII NetPBM.
+    P1,
+    /// ASCII NetPGM.
+    P2,
+    /// ASCII NetPPM.
+    P3,
+    /// binary NetPBM.
+    P4,
+    /// binary NetPGM.
+    P5,
+    /// binary NetPPM.
+    P6,
+    /// NetPAM.
+    P7,
+}
+
+impl Format {
+    /// whether the format stores its pixels in binary.
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Format::P4 | Format::P5 | Format::P6 | Format::P7)
+    }
+}
+
+/// the metadata at the start of a file, before its pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// the magic number of the file.
+    pub format: Format,
+    /// width of the image.
+    pub width: usize,
+    /// height of the image.
+    pub height: usize,
+    /// samples per pixel. only NetPAM files have this.
+    pub depth: Option<usize>,
+    /// maximum value of a sample. NetPBM files don't have this.
+    pub max_val: Option<u16>,
+    /// the TUPLTYPE name, which may be a custom one. only NetPAM files have this, and only if
+    /// TUPLTYPE is given.
+    pub tuple_type: Option<String>,
+    /// text of every comment, without the leading # and surrounding whitespace.
+    pub comments: Vec<String>,
+}
+
+/// how pixels are stored in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// pixels are written as text.
+    Ascii,
+    /// pixels are written as bytes.
+    Binary,
+}
+
+/// ways to combine two images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// add the samples together.
+    Add,
+    /// subtract the second sample from the first.
+    Subtract,
+    /// take the mean of the samples.
+    Average,
+    /// multiply the samples, which darkens.
+    Multiply,
+    /// multiply the inverted samples and invert the result, which lightens.
+    Screen,
+}
+
+/// weights of the red, green and blue channels when converting to grayscale.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LumaCoeffs {
+    /// ITU-R BT.601, used for standard definition video.
+    #[default]
+    Rec601,
+    /// ITU-R BT.709, used for high definition video and srgb.
+    Rec709,
+    /// custom rgb weights. must not be negative.
+    Custom([f64; 3]),
+}
+
+impl LumaCoeffs {
+    /// get the rgb weights.
+    pub fn weights(&self) -> [f64; 3] {
+        match self {
+            LumaCoeffs::Rec601 => [0.299, 0.587, 0.114],
+            LumaCoeffs::Rec709 => [0.2126, 0.7152, 0.0722],
+            LumaCoeffs::Custom(weights) => *weights,
+        }
+    }
+}
+
+/// byte order of 16-bit samples in binary files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    /// most significant byte first, as the spec requires.
+    #[default]
+    BigEndian,
+    /// least significant byte first. not standard, and most readers will misread it.
+    LittleEndian,
+}
+
+/// how loaders treat files that break the spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// return an error.
+    Strict,
+    /// fix the file where possible, like clamping samples larger than max_val or filling
+    /// missing pixels at the end with 0.
+    #[default]
+    Lenient,
 }
 
 /// universal type for all netpbm files.
+#[derive(Debug, Clone)]
 pub struct NetPBM<Class: NetPBMSaver> {
     class: Class,
+    source_format: Option<Format>,
+    comment: Option<String>,
 }
 
 /// type for NetPBM files.
+#[derive(Debug, Clone, PartialEq)]
 pub struct NetPBMFile {
     width: usize,
     height: usize,
@@ -27,6 +368,7 @@ pub struct NetPBMFile {
 }
 
 /// type for NetPGM files.
+#[derive(Debug, Clone, PartialEq)]
 pub struct NetPGMFile {
     width: usize,
     height: usize,
@@ -35,6 +377,7 @@ pub struct NetPGMFile {
 }
 
 /// type for NetPPM files.
+#[derive(Debug, Clone, PartialEq)]
 pub struct NetPPMFile {
     width: usize,
     height: usize,
@@ -45,11 +388,11 @@ pub struct NetPPMFile {
 impl<Class: NetPBMSaver> NetPBM<Class> {
     /// convert the image to its ASCII representation.
     ///
-    /// - comment - optional value to add a comment in the header.
+    /// - comment - optional value to add a comment in the header. defaults to the stored comment.
     ///
     /// returns - ASCII representation of the image.
     pub fn to_ascii(&self, comment: Option<&str>) -> String {
-        self.class.to_ascii(comment)
+        self.class.to_ascii(comment.or(self.comment.as_deref()))
     }
 
     /// convert the image to its binary representation.
@@ -59,19 +402,242 @@ impl<Class: NetPBMSaver> NetPBM<Class> {
         self.class.to_raw()
     }
 
+    /// convert the image to its binary representation, with a choice of 16-bit sample order.
+    ///
+    /// - byte_order - order of the bytes in 16-bit samples. little endian is not standard.
+    ///
+    /// returns - binary representation of the image.
+    pub fn to_raw_with(&self, byte_order: ByteOrder) -> Vec<u8> {
+        self.class.to_raw_with(byte_order)
+    }
+
+    /// append the binary representation of the image to a buffer.
+    ///
+    /// - buf - buffer to append to. its existing contents are kept.
+    pub fn write_raw_into(&self, buf: &mut Vec<u8>) {
+        self.class.write_raw_into(buf)
+    }
+
+    /// get the length of the binary representation without creating it.
+    ///
+    /// returns - the exact number of bytes to_raw produces.
+    pub fn raw_size(&self) -> usize {
+        self.class.raw_size()
+    }
+
+    /// get the length of the ASCII representation without creating it.
+    ///
+    /// returns - an upper bound on the number of bytes to_ascii produces without a comment.
+    pub fn ascii_size_estimate(&self) -> usize {
+        self.class.ascii_size_estimate()
+    }
+
+    /// get the inner image.
+    ///
+    /// returns - a reference to the inner image type.
+    pub fn inner(&self) -> &Class {
+        &self.class
+    }
+
+    /// unwrap the inner image.
+    ///
+    /// returns - the inner image type.
+    pub fn into_inner(self) -> Class {
+        self.class
+    }
+
+    /// the format the image was loaded from.
+    ///
+    /// returns - the magic number of the loaded file, or None if the image wasn't loaded.
+    pub fn source_format(&self) -> Option<Format> {
+        self.source_format
+    }
+
+    /// store a comment to write in the header of every ASCII representation.
+    ///
+    /// - comment - the comment. a comment given to to_ascii or save replaces it.
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.comment = Some(comment.into());
+    }
+
+    /// the stored comment.
+    ///
+    /// returns - the comment set with set_comment, or None if there isn't one.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// save the image in either representation.
+    ///
+    /// - path     - where to save the image.
+    /// - encoding - whether to save the ASCII or binary representation.
+    /// - comment  - optional value to add a comment in the header. only used for ASCII.
+    #[cfg(feature = "std")]
+    pub fn save(
+        &self,
+        path: &str,
+        encoding: Encoding,
+        comment: Option<&str>,
+    ) -> std::io::Result<()> {
+        match encoding {
+            Encoding::Ascii => self.save_ascii(path, comment),
+            Encoding::Binary => self.save_raw(path),
+        }
+    }
+
     /// save the image in its ASCII representation.
+    #[cfg(feature = "std")]
     pub fn save_ascii(&self, path: &str, comment: Option<&str>) -> std::io::Result<()> {
-        std::fs::write(path, self.class.to_ascii(comment))?;
+        std::fs::write(path, self.to_ascii(comment))?;
         Ok(())
     }
 
     /// save the image in its binary representation.
+    #[cfg(feature = "std")]
     pub fn save_raw(&self, path: &str) -> std::io::Result<()> {
         std::fs::write(path, self.class.to_raw())?;
         Ok(())
     }
 }
 
+/// writes pixels into an image one after another, left to right and top to bottom.
+pub struct PixelWriter<'a, T> {
+    pixels: &'a mut [Vec<T>],
+    width: usize,
+    max_val: u16,
+    check: fn(&T, u16) -> Result<(), PbmError>,
+    position: usize,
+}
+
+impl<T: Clone> PixelWriter<'_, T> {
+    /// write the next pixel.
+    ///
+    /// - value - value of the pixel. must be valid for the image.
+    ///
+    /// returns - an error if the image is already full or the value is larger than max_val.
+    pub fn push(&mut self, value: T) -> Result<(), PbmError> {
+        if self.remaining() == 0 {
+            return Err(PbmError::TooManyPixels);
+        }
+        (self.check)(&value, self.max_val)?;
+
+        self.pixels[self.position / self.width][self.position % self.width] = value;
+        self.position += 1;
+        Ok(())
+    }
+
+    /// write the same value to every pixel that hasn't been written yet.
+    ///
+    /// - value - value of the pixels. must be valid for the image.
+    pub fn fill_remaining(&mut self, value: T) -> Result<(), PbmError> {
+        (self.check)(&value, self.max_val)?;
+        while self.remaining() > 0 {
+            self.pixels[self.position / self.width][self.position % self.width] = value.clone();
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    /// get the number of pixels that can still be written.
+    pub fn remaining(&self) -> usize {
+        self.width * self.pixels.len() - self.position
+    }
+}
+
+impl<Class: NetPBMSaver> From<Class> for NetPBM<Class> {
+    fn from(class: Class) -> Self {
+        Self {
+            class,
+            source_format: None,
+            comment: None,
+        }
+    }
+}
+
+/// samples are rescaled from max_val to 255, so 16-bit images lose precision.
+#[cfg(feature = "image")]
+impl From<&NetPBM<NetPPMFile>> for image::RgbImage {
+    fn from(ppm: &NetPBM<NetPPMFile>) -> Self {
+        let max_val = ppm.class.max_val;
+        image::RgbImage::from_fn(ppm.class.width as u32, ppm.class.height as u32, |x, y| {
+            let color = ppm.class.pixels[y as usize][x as usize];
+            image::Rgb(color.map(|c| rescale_sample(c, max_val, 255) as u8))
+        })
+    }
+}
+
+/// the image has a max_val of 255.
+#[cfg(feature = "image")]
+impl From<&image::RgbImage> for NetPBM<NetPPMFile> {
+    fn from(rgb: &image::RgbImage) -> Self {
+        NetPPMFile {
+            width: rgb.width() as usize,
+            height: rgb.height() as usize,
+            max_val: 255,
+            pixels: rgb
+                .rows()
+                .map(|row| row.map(|pixel| pixel.0.map(u16::from)).collect())
+                .collect(),
+        }
+        .into()
+    }
+}
+
+/// samples are rescaled from max_val to 255, so 16-bit images lose precision.
+#[cfg(feature = "image")]
+impl From<&NetPBM<NetPGMFile>> for image::GrayImage {
+    fn from(pgm: &NetPBM<NetPGMFile>) -> Self {
+        let max_val = pgm.class.max_val;
+        image::GrayImage::from_fn(pgm.class.width as u32, pgm.class.height as u32, |x, y| {
+            let value = pgm.class.pixels[y as usize][x as usize];
+            image::Luma([rescale_sample(value, max_val, 255) as u8])
+        })
+    }
+}
+
+/// the image has a max_val of 255.
+#[cfg(feature = "image")]
+impl From<&image::GrayImage> for NetPBM<NetPGMFile> {
+    fn from(gray: &image::GrayImage) -> Self {
+        NetPGMFile {
+            width: gray.width() as usize,
+            height: gray.height() as usize,
+            max_val: 255,
+            pixels: gray
+                .rows()
+                .map(|row| row.map(|pixel| u16::from(pixel.0[0])).collect())
+                .collect(),
+        }
+        .into()
+    }
+}
+
+impl core::fmt::Display for NetPBM<NetPBMFile> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "PBM {}x{}", self.class.width, self.class.height)
+    }
+}
+
+impl core::fmt::Display for NetPBM<NetPGMFile> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "PGM {}x{} maxval={}",
+            self.class.width, self.class.height, self.class.max_val
+        )
+    }
+}
+
+impl core::fmt::Display for NetPBM<NetPPMFile> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "PPM {}x{} maxval={}",
+            self.class.width, self.class.height, self.class.max_val
+        )
+    }
+}
+
 impl NetPBMSaver for NetPBMFile {
     fn to_ascii(&self, comment: Option<&str>) -> String {
         let mut comment_text = String::new();
@@ -97,21 +663,29 @@ impl NetPBMSaver for NetPBMFile {
     }
 
     fn to_raw(&self) -> Vec<u8> {
-        let mut bits = Vec::new();
-        for (row_id, row) in self.pixels.iter().enumerate() {
-            for (i, v) in row.to_vec().iter().enumerate() {
-                if bits.len() <= row_id + i / 8 {
-                    bits.push(0);
-                }
-                bits[row_id + i / 8] |= u8::from(*v) << (7 - i % 8);
+        let mut raw = Vec::with_capacity(self.raw_size());
+        self.write_raw_into(&mut raw);
+        raw
+    }
+
+    fn write_raw_into_with(&self, buf: &mut Vec<u8>, _byte_order: ByteOrder) {
+        buf.extend_from_slice(format!("P4\n{} {}\n", self.width, self.height).as_bytes());
+        let row_bytes = self.width.div_ceil(8);
+        for row in &self.pixels {
+            let start = buf.len();
+            buf.resize(start + row_bytes, 0);
+            for (i, v) in row.iter().enumerate() {
+                buf[start + i / 8] |= u8::from(*v) << (7 - i % 8);
             }
         }
+    }
+
+    fn raw_size(&self) -> usize {
+        format!("P4\n{} {}\n", self.width, self.height).len() + self.width.div_ceil(8) * self.height
+    }
 
-        [
-            format!("P4\n{} {}\n", self.width, self.height).as_bytes(),
-            &bits,
-        ]
-        .concat()
+    fn ascii_size_estimate(&self) -> usize {
+        format!("P1\n{} {}\n", self.width, self.height).len() + self.width * self.height * 2 + 1
     }
 }
 
@@ -143,22 +717,34 @@ impl NetPBMSaver for NetPGMFile {
     }
 
     fn to_raw(&self) -> Vec<u8> {
-        [
+        self.to_raw_with(ByteOrder::BigEndian)
+    }
+
+    fn to_raw_with(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.raw_size());
+        self.write_raw_into_with(&mut raw, byte_order);
+        raw
+    }
+
+    fn write_raw_into_with(&self, buf: &mut Vec<u8>, byte_order: ByteOrder) {
+        buf.extend_from_slice(
             format!("P5\n{} {}\n{}\n", self.width, self.height, self.max_val).as_bytes(),
-            &self
-                .pixels
-                .iter()
-                .flatten()
-                .flat_map(|x| {
-                    if self.max_val > 255 {
-                        vec![(x >> 8) as u8, (x & 0xff) as u8]
-                    } else {
-                        vec![*x as u8]
-                    }
-                })
-                .collect::<Vec<u8>>(),
-        ]
-        .concat()
+        );
+        for x in self.pixels.iter().flatten() {
+            write_sample(buf, *x, self.max_val, byte_order);
+        }
+    }
+
+    fn raw_size(&self) -> usize {
+        format!("P5\n{} {}\n{}\n", self.width, self.height, self.max_val).len()
+            + self.width * self.height * bytes_per_sample(self.max_val)
+    }
+
+    fn ascii_size_estimate(&self) -> usize {
+        let len = format!("{}", self.max_val).len();
+        format!("P2\n{} {}\n{}\n", self.width, self.height, self.max_val).len()
+            + self.width * self.height * (len + 1)
+            + 1
     }
 }
 
@@ -193,631 +779,7724 @@ impl NetPBMSaver for NetPPMFile {
     }
 
     fn to_raw(&self) -> Vec<u8> {
-        [
-            format!("P6\n{} {}\n{}\n", self.width, self.height, self.max_val).as_bytes(),
-            &self
-                .pixels
-                .iter()
-                .flatten()
-                .flatten()
-                .flat_map(|x| {
-                    if self.max_val > 255 {
-                        vec![(x >> 8) as u8, (x & 0xff) as u8]
-                    } else {
-                        vec![*x as u8]
-                    }
-                })
-                .collect::<Vec<u8>>(),
-        ]
-        .concat()
+        self.to_raw_with(ByteOrder::BigEndian)
     }
-}
 
-impl NetPBM<NetPBMFile> {
-    /// create a new PBM File.
-    ///
-    /// - width      - immutable size for image width.
-    /// - height     - immutable size for image height.
-    pub fn new_pbm(width: usize, height: usize) -> Self {
-        let class = NetPBMFile {
-            width,
-            height,
-            pixels: vec![vec![false; width]; height],
-        };
-        Self { class }
+    fn to_raw_with(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.raw_size());
+        self.write_raw_into_with(&mut raw, byte_order);
+        raw
     }
 
-    /// set a pixels value.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    /// - value - value of pixel. false is white, true is black.
-    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
-        if x < self.class.width && y < self.class.height {
-            self.class.pixels[y][x] = value;
+    fn write_raw_into_with(&self, buf: &mut Vec<u8>, byte_order: ByteOrder) {
+        buf.extend_from_slice(
+            format!("P6\n{} {}\n{}\n", self.width, self.height, self.max_val).as_bytes(),
+        );
+        for x in self.pixels.iter().flatten().flatten() {
+            write_sample(buf, *x, self.max_val, byte_order);
         }
     }
 
-    /// get a pixels value.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    ///
-    /// returns - value of pixel. false is white, true is black.
-    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<bool> {
-        if x < self.class.width && y < self.class.height {
-            return Some(self.class.pixels[y][x]);
-        }
-        None
+    fn raw_size(&self) -> usize {
+        format!("P6\n{} {}\n{}\n", self.width, self.height, self.max_val).len()
+            + self.width * self.height * 3 * bytes_per_sample(self.max_val)
+    }
+
+    fn ascii_size_estimate(&self) -> usize {
+        let len = format!("{}", self.max_val).len();
+        format!("P3\n{} {}\n{}\n", self.width, self.height, self.max_val).len()
+            + self.width * self.height * 3 * (len + 1)
+            + 1
     }
 }
 
-impl NetPBM<NetPGMFile> {
-    /// create a new PGM File.
-    ///
-    /// - width      - immutable size for image width.
-    /// - height     - immutable size for image height.
-    pub fn new_pgm(width: usize, height: usize, max_val: u16) -> Self {
-        let class = NetPGMFile {
-            width,
-            height,
-            max_val,
-            pixels: vec![vec![0; width]; height],
-        };
-        Self { class }
+/// swap the axes of a grid of pixels, so that the output row x is the input column x.
+fn transpose_pixels<T: Clone>(pixels: &[Vec<T>], width: usize) -> Vec<Vec<T>> {
+    (0..width)
+        .map(|x| pixels.iter().map(|row| row[x].clone()).collect())
+        .collect()
+}
+
+/// value of a left to right gradient at column x, from 0 to max_val.
+fn gradient_value(x: usize, width: usize, max_val: u16) -> u16 {
+    if width <= 1 {
+        return 0;
     }
+    (x as u64 * max_val as u64 / (width as u64 - 1)) as u16
+}
 
-    /// set a pixels value.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    /// - value - value of pixel. 0 is black, max_val is white.
-    pub fn set_pixel(&mut self, x: usize, y: usize, value: u16) {
-        if x < self.class.width && y < self.class.height && value <= self.class.max_val {
-            self.class.pixels[y][x] = value;
-        }
+/// linearly interpolate between two samples at step i of len steps, rounding to the nearest value.
+fn lerp_sample(from: u16, to: u16, i: usize, len: usize) -> u16 {
+    if len <= 1 {
+        return from;
     }
+    let (from, to, i, steps) = (from as i64, to as i64, i as i64, len as i64 - 1);
+    let offset = (to - from) * i;
+    (from + (offset + offset.signum() * steps / 2) / steps) as u16
+}
 
-    /// get a pixels value.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    ///
-    /// returns - value of pixel. 0 is black, max_val is white.
-    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<u16> {
-        if x < self.class.width && y < self.class.height {
-            return Some(self.class.pixels[y][x]);
-        }
-        None
+/// rescale a sample from one maximum value to another, rounding to the nearest value.
+fn rescale_sample(sample: u16, old_max_val: u16, new_max_val: u16) -> u16 {
+    if old_max_val == 0 {
+        return 0;
     }
+    let scaled = (sample.min(old_max_val) as u64 * new_max_val as u64 * 2 + old_max_val as u64)
+        / (old_max_val as u64 * 2);
+    scaled as u16
 }
 
-impl NetPBM<NetPPMFile> {
-    /// create a new PPM File.
-    ///
-    /// - width      - immutable size for image width.
-    /// - height     - immutable size for image height.
-    pub fn new_ppm(width: usize, height: usize, max_val: u16) -> Self {
-        let class = NetPPMFile {
+/// convert a sample from 0.0 to 1.0 into a sample from 0 to max_val.
+#[cfg(feature = "std")]
+fn denormalize_sample(sample: f32, max_val: u16) -> u16 {
+    (sample.clamp(0.0, 1.0) * max_val as f32).round() as u16
+}
+
+/// make sure a rectangle fits inside an image and isn't empty.
+#[cfg(feature = "std")]
+fn check_rect(
+    image_width: usize,
+    image_height: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<(), PbmError> {
+    check_region(image_width, image_height, x, y, width, height)?;
+    if width > 0 && height > 0 {
+        Ok(())
+    } else {
+        Err(PbmError::OutOfBounds {
+            x,
+            y,
             width,
             height,
-            max_val,
-            pixels: vec![vec![[0; 3]; width]; height],
-        };
-        Self { class }
+        })
     }
+}
 
-    /// set a pixels color.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    /// - color - color of pixel. rgb order. 0 is black, max_val is white.
-    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u16; 3]) {
-        if x < self.class.width
-            && y < self.class.height
-            && color.iter().all(|x| x <= &self.class.max_val)
+/// make sure a rectangle fits inside an image. empty rectangles are allowed.
+fn check_region(
+    image_width: usize,
+    image_height: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Result<(), PbmError> {
+    let fits_x = x.checked_add(width).is_some_and(|end| end <= image_width);
+    let fits_y = y.checked_add(height).is_some_and(|end| end <= image_height);
+    if fits_x && fits_y {
+        Ok(())
+    } else {
+        Err(PbmError::OutOfBounds {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// sample a grid of channels at a fractional position, clamping the position to the grid.
+///
+/// - get    - reads the channels at a whole position.
+/// - x      - fractional x position.
+/// - y      - fractional y position.
+/// - width  - width of the grid. must not be 0.
+/// - height - height of the grid. must not be 0.
+#[cfg(feature = "std")]
+fn sample_bilinear_clamped<const N: usize>(
+    get: impl Fn(usize, usize) -> [u16; N],
+    x: f64,
+    y: f64,
+    width: usize,
+    height: usize,
+) -> [u16; N] {
+    let x = x.clamp(0.0, (width - 1) as f64);
+    let y = y.clamp(0.0, (height - 1) as f64);
+    let x0 = x.floor() as usize;
+    let y0 = y.floor() as usize;
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let fx = x - x0 as f64;
+    let fy = y - y0 as f64;
+
+    let top_left = get(x0, y0);
+    let top_right = get(x1, y0);
+    let bottom_left = get(x0, y1);
+    let bottom_right = get(x1, y1);
+
+    core::array::from_fn(|i| {
+        let top = top_left[i] as f64 * (1.0 - fx) + top_right[i] as f64 * fx;
+        let bottom = bottom_left[i] as f64 * (1.0 - fx) + bottom_right[i] as f64 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u16
+    })
+}
+
+/// number of bytes each sample takes up in a binary file.
+fn bytes_per_sample(max_val: u16) -> usize {
+    if max_val > 255 { 2 } else { 1 }
+}
+
+/// append one sample to a binary file, using two bytes if max_val needs them.
+fn write_sample(buf: &mut Vec<u8>, sample: u16, max_val: u16, byte_order: ByteOrder) {
+    if max_val > 255 {
+        match byte_order {
+            ByteOrder::BigEndian => buf.extend_from_slice(&sample.to_be_bytes()),
+            ByteOrder::LittleEndian => buf.extend_from_slice(&sample.to_le_bytes()),
+        }
+    } else {
+        buf.push(sample as u8);
+    }
+}
+
+/// read one big-endian sample from a binary file.
+fn read_sample(bytes: &[u8]) -> u16 {
+    bytes
+        .iter()
+        .fold(0, |sample, byte| (sample << 8) | *byte as u16)
+}
+
+/// make sure a row index and row length fit an image.
+fn check_row(width: usize, height: usize, y: usize, len: usize) -> Result<(), PbmError> {
+    if y >= height {
+        return Err(PbmError::OutOfBounds {
+            x: 0,
+            y,
+            width: len,
+            height: 1,
+        });
+    }
+    if len != width {
+        return Err(PbmError::DimensionMismatch {
+            expected: (width, 1),
+            found: (len, 1),
+        });
+    }
+    Ok(())
+}
+
+/// make sure a sample fits under the maximum value of an image.
+fn check_sample(value: u16, max_val: u16) -> Result<(), PbmError> {
+    if value > max_val {
+        return Err(PbmError::SampleOutOfRange { value, max_val });
+    }
+    Ok(())
+}
+
+/// check a sample read from a file against the maximum value of the image.
+///
+/// returns - the sample, clamped to max_val in lenient mode, or an error in strict mode.
+fn fit_sample(value: u16, max_val: u16, mode: ParseMode) -> Result<u16, PbmError> {
+    match mode {
+        ParseMode::Strict => check_sample(value, max_val).map(|_| value),
+        ParseMode::Lenient => Ok(value.min(max_val)),
+    }
+}
+
+/// replace the 4-connected region of pixels matching the pixel at (x, y).
+fn flood_fill_pixels<T: PartialEq + Clone>(pixels: &mut [Vec<T>], x: usize, y: usize, value: T) {
+    let Some(target) = pixels.get(y).and_then(|row| row.get(x)).cloned() else {
+        return;
+    };
+    if target == value {
+        return;
+    }
+
+    let mut stack = vec![(x, y)];
+    while let Some((x, y)) = stack.pop() {
+        if pixels[y][x] != target {
+            continue;
+        }
+        pixels[y][x] = value.clone();
+
+        if x > 0 {
+            stack.push((x - 1, y));
+        }
+        if x + 1 < pixels[y].len() {
+            stack.push((x + 1, y));
+        }
+        if y > 0 && x < pixels[y - 1].len() {
+            stack.push((x, y - 1));
+        }
+        if y + 1 < pixels.len() && x < pixels[y + 1].len() {
+            stack.push((x, y + 1));
+        }
+    }
+}
+
+/// make sure two images have the same size and max_val.
+fn check_same_shape(
+    (width, height, max_val): (usize, usize, u16),
+    (other_width, other_height, other_max_val): (usize, usize, u16),
+) -> Result<(), PbmError> {
+    if (width, height) != (other_width, other_height) {
+        return Err(PbmError::DimensionMismatch {
+            expected: (width, height),
+            found: (other_width, other_height),
+        });
+    }
+    if max_val != other_max_val {
+        return Err(PbmError::MaxValMismatch {
+            expected: max_val,
+            found: other_max_val,
+        });
+    }
+    Ok(())
+}
+
+/// combine two samples, clamping the result to max_val.
+fn blend_sample(a: u16, b: u16, max_val: u16, mode: BlendMode) -> u16 {
+    let (a, b, max_val) = (a as u32, b as u32, max_val as u32);
+    let blended = match mode {
+        BlendMode::Add => a + b,
+        BlendMode::Subtract => a.saturating_sub(b),
+        BlendMode::Average => (a + b) / 2,
+        BlendMode::Multiply if max_val == 0 => 0,
+        BlendMode::Multiply => a * b / max_val,
+        BlendMode::Screen if max_val == 0 => 0,
+        BlendMode::Screen => {
+            max_val - (max_val.saturating_sub(a) * max_val.saturating_sub(b)) / max_val
+        }
+    };
+    blended.min(max_val) as u16
+}
+
+/// rows of pixels.
+type Grid<T> = Vec<Vec<T>>;
+
+/// how far an image may outgrow its pixel data before a loader gives up on it.
+const PADDING_SLACK: usize = 1 << 16;
+
+/// make sure an image isn't much larger than the pixel data it's read from.
+///
+/// lenient loaders pad truncated data, so without this a tiny file could claim a huge size and
+/// exhaust memory. complete files always pass.
+///
+/// - needed    - the least number of bytes the pixels could be stored in.
+/// - height    - number of rows, which take up memory even when they are empty.
+/// - available - bytes of pixel data in the file.
+///
+/// returns - UnexpectedEof if the image needs more than twice the data plus PADDING_SLACK.
+fn check_padding(needed: usize, height: usize, available: usize) -> Result<(), PbmError> {
+    let limit = available.saturating_mul(2).saturating_add(PADDING_SLACK);
+    if needed.saturating_add(height) > limit {
+        return Err(PbmError::UnexpectedEof);
+    }
+    Ok(())
+}
+
+/// fill out short or missing rows with a blank pixel, so the grid is width by height.
+fn pad_pixels<T: Clone>(pixels: &mut Grid<T>, width: usize, height: usize, blank: T) {
+    for row in pixels.iter_mut() {
+        row.resize(width, blank.clone());
+    }
+    pixels.resize(height, vec![blank; width]);
+}
+
+/// parse the whitespace separated pixels of an ASCII file.
+///
+/// stops after width * height pixels, so trailing data is never read. memory is reserved based
+/// on the data length rather than the header, so a huge claimed size can't exhaust it up front.
+/// in lenient mode missing pixels are set to 0.
+///
+/// - data   - the file after its header.
+/// - offset - position of data in the file, for error messages.
+/// - width  - pixels per row.
+/// - height - number of rows.
+/// - mode   - whether to fix or reject samples larger than max_val and missing pixels.
+///
+/// returns - the pixels, and the position in the file right after the last sample, or
+///           InvalidHeader if width * height doesn't fit in a usize.
+fn parse_ascii_samples<const N: usize>(
+    data: &[u8],
+    offset: usize,
+    width: usize,
+    height: usize,
+    max_val: u16,
+    mode: ParseMode,
+) -> Result<(Grid<[u16; N]>, usize), PbmError> {
+    let count = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(N))
+        .ok_or(PbmError::InvalidHeader { offset })?;
+    check_padding(count, height, data.len())?;
+
+    let mut position = offset;
+    let mut end = offset;
+    let mut words = data
+        .split(u8::is_ascii_whitespace)
+        .map(|word| {
+            let word_offset = position;
+            position += word.len() + 1;
+            (word_offset, word)
+        })
+        .filter(|(_, word)| !word.is_empty());
+
+    let mut pixels = Vec::with_capacity(height.min(data.len()));
+    'rows: for y in 0..height {
+        let mut row = Vec::with_capacity(width.min(data.len()));
+        for x in 0..width {
+            let mut pixel = [0; N];
+            for sample in pixel.iter_mut() {
+                let Some((offset, word)) = words.next() else {
+                    if mode == ParseMode::Strict {
+                        return Err(PbmError::UnexpectedEof);
+                    }
+                    pixels.push(row);
+                    pad_pixels(&mut pixels, width, height, [0; N]);
+                    break 'rows;
+                };
+                let value = core::str::from_utf8(word)
+                    .ok()
+                    .and_then(|word| word.parse::<u16>().ok())
+                    .ok_or(PbmError::InvalidSample {
+                        offset,
+                        pixel: y * width + x,
+                    })?;
+                *sample = fit_sample(value, max_val, mode)?;
+                end = offset + word.len();
+            }
+            row.push(pixel);
+        }
+        pixels.push(row);
+    }
+    Ok((pixels, end))
+}
+
+/// build a normalized gaussian kernel reaching 3 sigma from its center.
+#[cfg(feature = "std")]
+fn gaussian_kernel(sigma: f64) -> Result<Vec<f64>, PbmError> {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return Err(PbmError::InvalidSigma { sigma });
+    }
+    let radius = (3.0 * sigma).ceil() as isize;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    Ok(kernel.into_iter().map(|weight| weight / sum).collect())
+}
+
+/// convolve a row-major plane of samples with a kernel, horizontally then vertically.
+///
+/// coordinates outside the plane are clamped to its edges.
+#[cfg(feature = "std")]
+fn convolve_plane(plane: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let radius = (kernel.len() / 2) as isize;
+    let pass = |plane: &[f64], horizontal: bool| -> Vec<f64> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| {
+                        let offset = k as isize - radius;
+                        let (sx, sy) = if horizontal {
+                            ((x + offset).clamp(0, width as isize - 1), y)
+                        } else {
+                            (x, (y + offset).clamp(0, height as isize - 1))
+                        };
+                        weight * plane[sy as usize * width + sx as usize]
+                    })
+                    .sum()
+            })
+            .collect()
+    };
+    pass(&pass(plane, true), false)
+}
+
+/// reads the header of a file one byte at a time, keeping track of the position.
+struct HeaderReader<I: Iterator<Item = u8>> {
+    bytes: core::iter::Peekable<I>,
+    offset: usize,
+    comments: Vec<String>,
+    /// newlines read so far.
+    lines: usize,
+    /// value of lines when the last word started.
+    word_line: usize,
+    /// offset of max_val, if it is on the same line as the height.
+    shared_max_val: Option<usize>,
+}
+
+impl<I: Iterator<Item = u8>> HeaderReader<I> {
+    fn new(bytes: I) -> Self {
+        Self {
+            bytes: bytes.peekable(),
+            offset: 0,
+            comments: Vec::new(),
+            lines: 0,
+            word_line: 0,
+            shared_max_val: None,
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = self.bytes.next()?;
+        self.offset += 1;
+        if byte == b'\n' {
+            self.lines += 1;
+        }
+        Some(byte)
+    }
+
+    /// consume the single whitespace byte that must follow a magic number.
+    /// any whitespace works, not just a newline. a comment may also start right away.
+    fn end_magic(&mut self) -> Result<(), PbmError> {
+        match self.bytes.peek() {
+            Some(byte) if byte.is_ascii_whitespace() => {
+                self.next_byte();
+                Ok(())
+            }
+            Some(b'#') => Ok(()),
+            _ => Err(PbmError::InvalidHeader {
+                offset: self.offset,
+            }),
+        }
+    }
+
+    /// read the rest of a line, consuming the newline.
+    fn read_line(&mut self) -> String {
+        let mut line = Vec::new();
+        while let Some(byte) = self.next_byte()
+            && byte != b'\n'
         {
-            self.class.pixels[y][x] = color;
+            line.push(byte);
         }
+        String::from_utf8_lossy(&line).trim().into()
     }
 
-    /// get a pixels color.
+    /// skip whitespace and comments, then read a word and the single whitespace byte after it.
     ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    ///
-    /// returns - color of pixel. rgb order. 0 is black, max_val is white.
-    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<[u16; 3]> {
-        if x < self.class.width && y < self.class.height {
-            return Some(self.class.pixels[y][x]);
+    /// a CRLF between fields is skipped like any other whitespace, but after the last field only
+    /// its CR is consumed, since binary pixel data may well start with a LF byte.
+    fn next_word(&mut self) -> Result<(usize, String), PbmError> {
+        loop {
+            match self.bytes.peek() {
+                Some(byte) if byte.is_ascii_whitespace() => {
+                    self.next_byte();
+                }
+                Some(b'#') => {
+                    self.next_byte();
+                    let comment = self.read_line();
+                    self.comments.push(comment);
+                }
+                Some(_) => break,
+                None => {
+                    return Err(PbmError::InvalidHeader {
+                        offset: self.offset,
+                    });
+                }
+            }
         }
-        None
+
+        let offset = self.offset;
+        self.word_line = self.lines;
+        let mut word = Vec::new();
+        while let Some(byte) = self.bytes.peek() {
+            if *byte == b'#' {
+                break;
+            }
+            let byte = self.next_byte().unwrap();
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            word.push(byte);
+        }
+        let word = String::from_utf8(word).map_err(|_| PbmError::InvalidHeader { offset })?;
+        Ok((offset, word))
+    }
+
+    fn next_number<T: core::str::FromStr>(&mut self) -> Result<T, PbmError> {
+        let (offset, word) = self.next_word()?;
+        word.parse().map_err(|_| PbmError::InvalidHeader { offset })
+    }
+
+    /// read a number for formats without comments, like PFM, where # is just an invalid byte.
+    /// skips whitespace before it and consumes the single whitespace byte after it.
+    fn next_plain_number<T: core::str::FromStr>(&mut self) -> Result<T, PbmError> {
+        while self.bytes.peek().is_some_and(u8::is_ascii_whitespace) {
+            self.next_byte();
+        }
+        let offset = self.offset;
+        let mut word = Vec::new();
+        while let Some(byte) = self.next_byte() {
+            if byte.is_ascii_whitespace() {
+                break;
+            }
+            word.push(byte);
+        }
+        core::str::from_utf8(&word)
+            .ok()
+            .and_then(|word| word.parse().ok())
+            .ok_or(PbmError::InvalidHeader { offset })
     }
 }
 
-/// image types for NetPAM files.
-pub enum TupleType {
-    /// like NetPBM.
-    BlackAndWhite,
-    /// like NetPGM.
-    Grayscale,
-    /// like NetPPM.
-    RGB,
-    /// like NetPBM, but with transparency.
-    BlackAndWhiteAlpha,
-    /// like NetPGM, but with transparency.
-    GrayscaleAlpha,
-    /// like NetPPM, but with transparency.
-    RGBAlpha,
-    /// allows for custom extensible formats to NetPAM.
-    Custom {
-        /// the quantity of bytes per pixel.
-        depth: u16,
-        /// the name used in the header. SHOULD BE UNIQUE!
-        tuple_type: &'static str,
-    },
+/// parse a header, leaving the reader at the first byte of pixel data.
+///
+/// fields can be split by any whitespace, so width, height and max_val may share one line,
+/// and the magic number may be followed by a space instead of a newline. strict pgm loading
+/// rejects a shared max_val line afterwards.
+fn parse_header<I: Iterator<Item = u8>>(reader: &mut HeaderReader<I>) -> Result<Header, PbmError> {
+    let start = reader.offset;
+    let format = match [reader.next_byte(), reader.next_byte()] {
+        [Some(b'P'), Some(b'1')] => Format::P1,
+        [Some(b'P'), Some(b'2')] => Format::P2,
+        [Some(b'P'), Some(b'3')] => Format::P3,
+        [Some(b'P'), Some(b'4')] => Format::P4,
+        [Some(b'P'), Some(b'5')] => Format::P5,
+        [Some(b'P'), Some(b'6')] => Format::P6,
+        [Some(b'P'), Some(b'7')] => Format::P7,
+        _ => return Err(PbmError::InvalidHeader { offset: start }),
+    };
+    reader.end_magic()?;
+
+    if format == Format::P7 {
+        return parse_pam_header(reader);
+    }
+
+    let width = reader.next_number()?;
+    let height = reader.next_number()?;
+    let height_line = reader.word_line;
+    let max_val = match format {
+        Format::P1 | Format::P4 => None,
+        _ => {
+            let (offset, word) = reader.next_word()?;
+            if reader.word_line == height_line {
+                reader.shared_max_val = Some(offset);
+            }
+            Some(
+                word.parse()
+                    .map_err(|_| PbmError::InvalidHeader { offset })?,
+            )
+        }
+    };
+
+    Ok(Header {
+        format,
+        width,
+        height,
+        depth: None,
+        max_val,
+        tuple_type: None,
+        comments: core::mem::take(&mut reader.comments),
+    })
+}
+
+/// parse the lines of a pam header after its magic number, up to and including ENDHDR.
+fn parse_pam_header<I: Iterator<Item = u8>>(
+    reader: &mut HeaderReader<I>,
+) -> Result<Header, PbmError> {
+    let mut width = None;
+    let mut height = None;
+    let mut depth = None;
+    let mut max_val = None;
+    let mut tuple_type: Option<String> = None;
+
+    loop {
+        let offset = reader.offset;
+        if reader.bytes.peek().is_none() {
+            return Err(PbmError::InvalidHeader { offset });
+        }
+        let line = reader.read_line();
+        if let Some(comment) = line.strip_prefix('#') {
+            reader.comments.push(comment.trim().into());
+            continue;
+        }
+
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+        let value = value.trim();
+        let number = || {
+            value
+                .parse()
+                .map_err(|_| PbmError::InvalidHeader { offset })
+        };
+        match key {
+            "" => {}
+            "ENDHDR" => break,
+            "WIDTH" => width = Some(number()?),
+            "HEIGHT" => height = Some(number()?),
+            "DEPTH" => {
+                let value = number()?;
+                if value == 0 || u16::try_from(value).is_err() {
+                    return Err(PbmError::InvalidHeader { offset });
+                }
+                depth = Some(value)
+            }
+            "MAXVAL" => {
+                max_val = Some(
+                    value
+                        .parse()
+                        .map_err(|_| PbmError::InvalidHeader { offset })?,
+                )
+            }
+            "TUPLTYPE" => {
+                tuple_type = Some(match tuple_type {
+                    Some(previous) => format!("{} {}", previous, value),
+                    None => value.into(),
+                })
+            }
+            _ => return Err(PbmError::InvalidHeader { offset }),
+        }
+    }
+
+    let (Some(width), Some(height), Some(depth), Some(max_val)) = (width, height, depth, max_val)
+    else {
+        return Err(PbmError::InvalidHeader {
+            offset: reader.offset,
+        });
+    };
+
+    Ok(Header {
+        format: Format::P7,
+        width,
+        height,
+        depth: Some(depth),
+        max_val: Some(max_val),
+        tuple_type,
+        comments: core::mem::take(&mut reader.comments),
+    })
+}
+
+/// fill a new grid by mapping each of its pixels back into a source grid.
+///
+/// - get        - returns the samples of the source pixel at (x, y).
+/// - width      - width of the source grid.
+/// - height     - height of the source grid.
+/// - out_width  - width of the new grid.
+/// - out_height - height of the new grid.
+/// - map        - turns the position of a new pixel into a fractional source position.
+/// - fill       - samples of new pixels that map outside the source grid.
+#[cfg(feature = "std")]
+fn warp_pixels<const N: usize>(
+    get: impl Fn(usize, usize) -> [u16; N],
+    width: usize,
+    height: usize,
+    out_width: usize,
+    out_height: usize,
+    map: impl Fn(f64, f64) -> (f64, f64),
+    fill: [u16; N],
+) -> Vec<Vec<[u16; N]>> {
+    (0..out_height)
+        .map(|y| {
+            (0..out_width)
+                .map(|x| {
+                    let (sx, sy) = map(x as f64, y as f64);
+                    if width == 0
+                        || height == 0
+                        || !(-0.5..width as f64 - 0.5).contains(&sx)
+                        || !(-0.5..height as f64 - 0.5).contains(&sy)
+                    {
+                        return fill;
+                    }
+                    sample_bilinear_clamped(&get, sx, sy, width, height)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// size of the canvas that fits an image rotated around its center.
+///
+/// returns - the new width, new height, and a map from new pixels to original pixels.
+#[cfg(feature = "std")]
+fn rotated_bounds(
+    width: usize,
+    height: usize,
+    degrees: f64,
+) -> (usize, usize, impl Fn(f64, f64) -> (f64, f64)) {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (w, h) = (width as f64, height as f64);
+    // round away tiny errors, so rotating by a multiple of 90 degrees doesn't grow the canvas.
+    let out_width = ((w * cos.abs() + h * sin.abs()) - 1e-9).ceil().max(0.0) as usize;
+    let out_height = ((w * sin.abs() + h * cos.abs()) - 1e-9).ceil().max(0.0) as usize;
+    let (out_w, out_h) = (out_width as f64, out_height as f64);
+    let map = move |x: f64, y: f64| {
+        let dx = x + 0.5 - out_w / 2.0;
+        let dy = y + 0.5 - out_h / 2.0;
+        (
+            dx * cos - dy * sin + w / 2.0 - 0.5,
+            dx * sin + dy * cos + h / 2.0 - 0.5,
+        )
+    };
+    (out_width, out_height, map)
+}
+
+/// shear a grid, growing it to fit, with nearest neighbour sampling.
+///
+/// a pixel at (x, y) moves to (x + shear_x * y, y + shear_y * x).
+///
+/// returns - the new width, new height and pixels.
+#[cfg(feature = "std")]
+fn shear_pixels<T: Copy>(
+    pixels: &[Vec<T>],
+    width: usize,
+    height: usize,
+    shear_x: f64,
+    shear_y: f64,
+    fill: T,
+) -> (usize, usize, Vec<Vec<T>>) {
+    let (w, h) = (width as f64, height as f64);
+    let xs = [0.0, w, shear_x * h, w + shear_x * h];
+    let ys = [0.0, shear_y * w, h, shear_y * w + h];
+    let min_x = xs.iter().copied().fold(f64::INFINITY, f64::min);
+    let min_y = ys.iter().copied().fold(f64::INFINITY, f64::min);
+    let max_x = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let max_y = ys.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let out_width = ((max_x - min_x) - 1e-9).ceil().max(0.0) as usize;
+    let out_height = ((max_y - min_y) - 1e-9).ceil().max(0.0) as usize;
+
+    let determinant = 1.0 - shear_x * shear_y;
+    let out_pixels = (0..out_height)
+        .map(|y| {
+            (0..out_width)
+                .map(|x| {
+                    let px = x as f64 + 0.5 + min_x;
+                    let py = y as f64 + 0.5 + min_y;
+                    let sx = (px - shear_x * py) / determinant;
+                    let sy = (py - shear_y * px) / determinant;
+                    if (0.0..w).contains(&sx) && (0.0..h).contains(&sy) {
+                        pixels[sy as usize][sx as usize]
+                    } else {
+                        fill
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    (out_width, out_height, out_pixels)
+}
+
+/// invert an affine matrix, giving a map from destination pixels to source pixels.
+///
+/// a singular matrix maps every pixel to NaN, so nothing is sampled.
+#[cfg(feature = "std")]
+fn inverse_affine_map(matrix: [[f64; 3]; 2]) -> impl Fn(f64, f64) -> (f64, f64) {
+    let [[a, b, c], [d, e, f]] = matrix;
+    let determinant = a * e - b * d;
+    move |x: f64, y: f64| {
+        let px = x + 0.5 - c;
+        let py = y + 0.5 - f;
+        (
+            (e * px - b * py) / determinant - 0.5,
+            (a * py - d * px) / determinant - 0.5,
+        )
+    }
+}
+
+/// make sure a grid of pixels has exactly height rows of width pixels.
+fn check_grid<T>(pixels: &[Vec<T>], width: usize, height: usize) -> Result<(), PbmError> {
+    if pixels.len() != height {
+        return Err(PbmError::DimensionMismatch {
+            expected: (width, height),
+            found: (width, pixels.len()),
+        });
+    }
+    for (row, pixels) in pixels.iter().enumerate() {
+        if pixels.len() != width {
+            return Err(PbmError::RowLengthMismatch {
+                row,
+                expected: width,
+                found: pixels.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// shrink a grid by averaging blocks of pixels.
+///
+/// blocks on the right and bottom edges only average the pixels they cover.
+///
+/// - get    - returns the samples of the pixel at (x, y).
+/// - factor - width and height of each block. must not be 0.
+fn downsample_pixels<const N: usize>(
+    get: impl Fn(usize, usize) -> [u16; N],
+    width: usize,
+    height: usize,
+    factor: usize,
+) -> Vec<Vec<[u16; N]>> {
+    (0..height.div_ceil(factor))
+        .map(|by| {
+            (0..width.div_ceil(factor))
+                .map(|bx| {
+                    let xs = bx * factor..((bx + 1) * factor).min(width);
+                    let ys = by * factor..((by + 1) * factor).min(height);
+                    let count = (xs.len() * ys.len()) as u64;
+                    let mut sums = [0u64; N];
+                    for y in ys {
+                        for x in xs.clone() {
+                            for (sum, value) in sums.iter_mut().zip(get(x, y)) {
+                                *sum += value as u64;
+                            }
+                        }
+                    }
+                    sums.map(|sum| ((sum + count / 2) / count) as u16)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// erode or dilate a black and white grid with a square of the given radius.
+///
+/// pixels outside the grid count as unset.
+fn morph_pixels(
+    pixels: &[Vec<bool>],
+    width: usize,
+    height: usize,
+    radius: usize,
+    dilate: bool,
+) -> Vec<Vec<bool>> {
+    let window = |len: usize, i: usize| {
+        let start = i.checked_sub(radius);
+        let end = i + radius + 1;
+        (
+            start.unwrap_or(0)..end.min(len),
+            start.is_some() && end <= len,
+        )
+    };
+    let combine =
+        |mut values: core::ops::Range<usize>, inside: bool, get: &dyn Fn(usize) -> bool| {
+            if dilate {
+                values.any(get)
+            } else {
+                inside && values.all(get)
+            }
+        };
+
+    let rows: Vec<Vec<bool>> = pixels
+        .iter()
+        .map(|row| {
+            (0..width)
+                .map(|x| {
+                    let (range, inside) = window(width, x);
+                    combine(range, inside, &|i| row[i])
+                })
+                .collect()
+        })
+        .collect();
+    (0..height)
+        .map(|y| {
+            let (range, inside) = window(height, y);
+            (0..width)
+                .map(|x| combine(range.clone(), inside, &|i| rows[i][x]))
+                .collect()
+        })
+        .collect()
+}
+
+/// find the connected top to bottom path of pixels with the lowest total gradient.
+///
+/// returns - the x position of the path in every row.
+fn min_vertical_seam(pixels: &[Vec<[u16; 3]>], width: usize) -> Vec<usize> {
+    let height = pixels.len();
+    let channel_diff = |a: [u16; 3], b: [u16; 3]| -> u64 {
+        a.iter().zip(b).map(|(a, b)| a.abs_diff(b) as u64).sum()
+    };
+    let energy = |x: usize, y: usize| -> u64 {
+        let left = pixels[y][x.saturating_sub(1)];
+        let right = pixels[y][(x + 1).min(width - 1)];
+        let up = pixels[y.saturating_sub(1)][x];
+        let down = pixels[(y + 1).min(height - 1)][x];
+        channel_diff(left, right) + channel_diff(up, down)
+    };
+
+    let mut costs: Vec<Vec<u64>> = vec![(0..width).map(|x| energy(x, 0)).collect()];
+    for y in 1..height {
+        let previous = &costs[y - 1];
+        let row = (0..width)
+            .map(|x| {
+                let best = previous[x.saturating_sub(1)..(x + 2).min(width)]
+                    .iter()
+                    .min()
+                    .unwrap();
+                best + energy(x, y)
+            })
+            .collect();
+        costs.push(row);
+    }
+
+    let mut seam = vec![0usize; height];
+    let mut x = (0..width).min_by_key(|x| costs[height - 1][*x]).unwrap();
+    for y in (0..height).rev() {
+        if y + 1 < height {
+            let above = seam[y + 1];
+            x = (above.saturating_sub(1)..(above + 2).min(width))
+                .min_by_key(|x| costs[y][*x])
+                .unwrap();
+        }
+        seam[y] = x;
+    }
+    seam
+}
+
+/// hash bytes with 64-bit FNV-1a, which is stable across runs and platforms.
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// make sure a lookup table has an entry for every sample, and every entry fits under max_val.
+fn check_lut(lut: &[u16], max_val: u16) -> Result<(), PbmError> {
+    if lut.len() != max_val as usize + 1 {
+        return Err(PbmError::DimensionMismatch {
+            expected: (max_val as usize + 1, 1),
+            found: (lut.len(), 1),
+        });
+    }
+    for value in lut {
+        check_sample(*value, max_val)?;
+    }
+    Ok(())
+}
+
+/// parse the header of the image that starts at start in file.
+///
+/// returns - the header, and the position of the first byte of pixel data.
+fn read_header_at(file: &[u8], start: usize) -> Result<(Header, usize), PbmError> {
+    read_header_layout(file, start).map(|(header, data_start, _)| (header, data_start))
 }
 
-impl TupleType {
-    fn get_depth(&self) -> u16 {
-        match self {
-            TupleType::BlackAndWhite => 1,
-            TupleType::Grayscale => 1,
-            TupleType::RGB => 3,
-            TupleType::BlackAndWhiteAlpha => 2,
-            TupleType::GrayscaleAlpha => 2,
-            TupleType::RGBAlpha => 4,
-            TupleType::Custom { depth, .. } => *depth,
-        }
+/// parse the header of the image that starts at start in file, noting how it is laid out.
+///
+/// returns - the header, the position of the first byte of pixel data, and the position of
+/// max_val if it is on the same line as the height.
+fn read_header_layout(
+    file: &[u8],
+    start: usize,
+) -> Result<(Header, usize, Option<usize>), PbmError> {
+    let mut reader = HeaderReader::new(file[start..].iter().copied());
+    reader.offset = start;
+    let header = parse_header(&mut reader)?;
+    Ok((header, reader.offset, reader.shared_max_val))
+}
+
+/// work out how many bytes the pixels of a binary image take up.
+///
+/// - width       - pixels per row.
+/// - pixel_bytes - bytes per pixel.
+/// - height      - number of rows.
+/// - offset      - position of the header in the file, for error messages.
+///
+/// returns - the size of one row and of all rows together, or InvalidHeader if either doesn't
+///           fit in a usize.
+fn binary_size(
+    width: usize,
+    pixel_bytes: usize,
+    height: usize,
+    offset: usize,
+) -> Result<(usize, usize), PbmError> {
+    width
+        .checked_mul(pixel_bytes)
+        .and_then(|row_bytes| Some((row_bytes, row_bytes.checked_mul(height)?)))
+        .ok_or(PbmError::InvalidHeader { offset })
+}
+
+/// split binary pixel data into rows, stopping early if the data runs out.
+///
+/// every row has row_bytes bytes, except a truncated last row.
+fn binary_rows(data: &[u8], row_bytes: usize, height: usize) -> impl Iterator<Item = &[u8]> {
+    let available = data.len().min(row_bytes.saturating_mul(height));
+    let rows = if row_bytes == 0 {
+        height
+    } else {
+        available.div_ceil(row_bytes)
+    };
+    (0..rows).map(move |y| &data[y * row_bytes..((y + 1) * row_bytes).min(available)])
+}
+
+/// convert an srgb encoded value from 0.0 to 1.0 to linear light.
+#[cfg(feature = "std")]
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// convert a linear light value from 0.0 to 1.0 to srgb encoding.
+#[cfg(feature = "std")]
+fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// cyclically shift every pixel dx to the right and dy down, wrapping around the edges.
+fn roll_pixels<T>(pixels: &mut [Vec<T>], dx: isize, dy: isize) {
+    if !pixels.is_empty() {
+        pixels.rotate_right(dy.rem_euclid(pixels.len() as isize) as usize);
+    }
+    for row in pixels.iter_mut().filter(|row| !row.is_empty()) {
+        let len = row.len() as isize;
+        row.rotate_right(dx.rem_euclid(len) as usize);
+    }
+}
+
+/// find the smallest rectangle holding every pixel that isn't part of the border.
+///
+/// returns - x, y, width and height of the rectangle, or None if every pixel is border.
+fn content_bounds<T>(
+    pixels: &[Vec<T>],
+    is_border: impl Fn(&T) -> bool,
+) -> Option<(usize, usize, usize, usize)> {
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (y, row) in pixels.iter().enumerate() {
+        for (x, pixel) in row.iter().enumerate() {
+            if !is_border(pixel) {
+                let (x0, y0, x1, y1) = bounds.unwrap_or((x, y, x, y));
+                bounds = Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y)));
+            }
+        }
+    }
+    bounds.map(|(x0, y0, x1, y1)| (x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+/// copy a rectangle out of a grid of pixels. the rectangle must fit inside the grid.
+fn crop_pixels<T: Clone>(
+    pixels: &[Vec<T>],
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Grid<T> {
+    pixels[y..y + height]
+        .iter()
+        .map(|row| row[x..x + width].to_vec())
+        .collect()
+}
+
+/// greatest common divisor of two numbers. gcd(0, b) is b.
+fn gcd(mut a: u16, mut b: u16) -> u16 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// set every pixel on a row or column that is a multiple of spacing to value.
+fn grid_pixels<T: Clone>(pixels: &mut [Vec<T>], spacing: usize, value: T) {
+    if spacing == 0 {
+        return;
+    }
+    for (y, row) in pixels.iter_mut().enumerate() {
+        for (x, pixel) in row.iter_mut().enumerate() {
+            if y.is_multiple_of(spacing) || x.is_multiple_of(spacing) {
+                *pixel = value.clone();
+            }
+        }
+    }
+}
+
+/// iterate over a rectangle of a grid of pixels, row by row. the rectangle must fit inside the grid.
+fn region_iter<T>(
+    pixels: &[Vec<T>],
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, usize, &T)> {
+    pixels[y..y + height]
+        .iter()
+        .zip(y..)
+        .flat_map(move |(row, py)| {
+            row[x..x + width]
+                .iter()
+                .zip(x..)
+                .map(move |(pixel, px)| (px, py, pixel))
+        })
+}
+
+/// squared euclidean distance between two colors in rgb space.
+fn color_distance_squared(a: [u16; 3], b: [u16; 3]) -> u64 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| u64::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+impl NetPBM<NetPBMFile> {
+    /// create a new PBM File.
+    ///
+    /// - width      - immutable size for image width.
+    /// - height     - immutable size for image height.
+    pub fn new_pbm(width: usize, height: usize) -> Self {
+        let class = NetPBMFile {
+            width,
+            height,
+            pixels: vec![vec![false; width]; height],
+        };
+        Self {
+            class,
+            source_format: None,
+            comment: None,
+        }
+    }
+
+    /// create a PBM File from a function of pixel positions.
+    ///
+    /// - width  - immutable size for image width.
+    /// - height - immutable size for image height.
+    /// - f      - called with (x, y) for every pixel, returning its value.
+    pub fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> bool) -> Self {
+        let mut pbm = Self::new_pbm(width, height);
+        for (y, row) in pbm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = f(x, y);
+            }
+        }
+        pbm
+    }
+
+    /// create a checkerboard PBM File.
+    ///
+    /// - width       - immutable size for image width.
+    /// - height      - immutable size for image height.
+    /// - square_size - width and height of each square. treated as 1 if 0.
+    ///
+    /// returns - a checkerboard starting with a white square in the top left.
+    pub fn checkerboard(width: usize, height: usize, square_size: usize) -> Self {
+        let square_size = square_size.max(1);
+        let mut pbm = Self::new_pbm(width, height);
+        for (y, row) in pbm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = (x / square_size + y / square_size) % 2 == 1;
+            }
+        }
+        pbm
+    }
+
+    /// set a pixels value.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - value - value of pixel. false is white, true is black.
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: bool) {
+        if x < self.class.width && y < self.class.height {
+            self.class.pixels[y][x] = value;
+        }
+    }
+
+    /// set a pixels value at signed coordinates.
+    ///
+    /// - x     - x position of pixel. does nothing if negative or not in image.
+    /// - y     - y position of pixel. does nothing if negative or not in image.
+    /// - value - value of pixel. false is white, true is black.
+    pub fn set_pixel_signed(&mut self, x: i32, y: i32, value: bool) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.set_pixel(x, y, value);
+        }
+    }
+
+    /// get a pixels value.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - value of pixel. false is white, true is black.
+    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<bool> {
+        if x < self.class.width && y < self.class.height {
+            return Some(self.class.pixels[y][x]);
+        }
+        None
+    }
+
+    /// set every pixel to black.
+    pub fn clear_black(&mut self) {
+        for row in &mut self.class.pixels {
+            row.fill(true);
+        }
+    }
+
+    /// set every pixel to white.
+    pub fn clear_white(&mut self) {
+        for row in &mut self.class.pixels {
+            row.fill(false);
+        }
+    }
+
+    /// replace a whole row of pixels.
+    ///
+    /// - y   - y position of the row.
+    /// - row - values of the pixels, left to right. must be as long as the image is wide.
+    pub fn set_row(&mut self, y: usize, row: &[bool]) -> Result<(), PbmError> {
+        check_row(self.class.width, self.class.height, y, row.len())?;
+        self.class.pixels[y].copy_from_slice(row);
+        Ok(())
+    }
+
+    /// get a whole row of pixels.
+    ///
+    /// - y - y position of the row.
+    ///
+    /// returns - values of the pixels, left to right, or None if the row is not in the image.
+    pub fn get_row(&self, y: usize) -> Option<&[bool]> {
+        self.class.pixels.get(y).map(Vec::as_slice)
+    }
+
+    /// write pixels one after another, left to right and top to bottom.
+    ///
+    /// returns - a writer starting at the top left pixel.
+    pub fn writer(&mut self) -> PixelWriter<'_, bool> {
+        PixelWriter {
+            pixels: &mut self.class.pixels,
+            width: self.class.width,
+            max_val: 1,
+            check: |_, _| Ok(()),
+            position: 0,
+        }
+    }
+
+    /// replace the region of pixels connected to (x, y) that match its value.
+    ///
+    /// pixels are connected through their left, right, top and bottom neighbours.
+    ///
+    /// - x     - x position of the starting pixel. does nothing if not in image.
+    /// - y     - y position of the starting pixel. does nothing if not in image.
+    /// - value - new value of the region.
+    pub fn flood_fill(&mut self, x: usize, y: usize, value: bool) {
+        flood_fill_pixels(&mut self.class.pixels, x, y, value);
+    }
+
+    /// convert the image to its ASCII representation, wrapping long rows.
+    ///
+    /// every row starts on a new line, and no line has trailing whitespace.
+    ///
+    /// - line_width - maximum number of characters in a line of pixels. treated as 1 if 0.
+    ///
+    /// returns - ASCII representation of the image.
+    pub fn to_ascii_wrapped(&self, line_width: usize) -> String {
+        let mut text = format!("P1\n{} {}\n", self.class.width, self.class.height);
+        for row in &self.class.pixels {
+            let mut line_len = 0;
+            for pixel in row {
+                if line_len > 0 && line_len + 2 > line_width {
+                    text.push('\n');
+                    line_len = 0;
+                }
+                if line_len > 0 {
+                    text.push(' ');
+                    line_len += 1;
+                }
+                text.push(if *pixel { '1' } else { '0' });
+                line_len += 1;
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// shear the image, with nearest neighbour sampling.
+    ///
+    /// a pixel at (x, y) moves to (x + shear_x * y, y + shear_y * x), and the image grows to fit.
+    /// if shear_x * shear_y is 1 the image collapses to a line, and every pixel is fill.
+    ///
+    /// - shear_x - horizontal shift per row.
+    /// - shear_y - vertical shift per column.
+    /// - fill    - value of pixels outside the original image.
+    ///
+    /// returns - the sheared image.
+    #[cfg(feature = "std")]
+    pub fn shear(&self, shear_x: f64, shear_y: f64, fill: bool) -> Self {
+        let (width, height, pixels) = shear_pixels(
+            &self.class.pixels,
+            self.class.width,
+            self.class.height,
+            shear_x,
+            shear_y,
+            fill,
+        );
+        NetPBMFile {
+            width,
+            height,
+            pixels,
+        }
+        .into()
+    }
+
+    /// check that the image is internally consistent.
+    ///
+    /// returns - an error if there aren't height rows of width pixels.
+    pub fn validate(&self) -> Result<(), PbmError> {
+        check_grid(&self.class.pixels, self.class.width, self.class.height)
+    }
+
+    /// count the set pixels.
+    ///
+    /// returns - the number of true pixels.
+    pub fn count_set(&self) -> usize {
+        self.class.pixels.iter().flatten().filter(|x| **x).count()
+    }
+
+    /// get the fraction of the image that is set.
+    ///
+    /// returns - the number of true pixels divided by the number of pixels, or 0.0 if the image is empty.
+    pub fn coverage(&self) -> f64 {
+        let total = self.class.width * self.class.height;
+        if total == 0 {
+            return 0.0;
+        }
+        self.count_set() as f64 / total as f64
+    }
+
+    /// shrink the set regions of the image.
+    ///
+    /// a pixel stays set only if every pixel within radius of it, in a square, is set. pixels
+    /// outside the image count as unset.
+    ///
+    /// - radius - distance from the center to the edge of the square.
+    ///
+    /// returns - the eroded image.
+    pub fn erode(&self, radius: usize) -> Self {
+        NetPBMFile {
+            width: self.class.width,
+            height: self.class.height,
+            pixels: morph_pixels(
+                &self.class.pixels,
+                self.class.width,
+                self.class.height,
+                radius,
+                false,
+            ),
+        }
+        .into()
+    }
+
+    /// grow the set regions of the image.
+    ///
+    /// a pixel becomes set if any pixel within radius of it, in a square, is set.
+    ///
+    /// - radius - distance from the center to the edge of the square.
+    ///
+    /// returns - the dilated image.
+    pub fn dilate(&self, radius: usize) -> Self {
+        NetPBMFile {
+            width: self.class.width,
+            height: self.class.height,
+            pixels: morph_pixels(
+                &self.class.pixels,
+                self.class.width,
+                self.class.height,
+                radius,
+                true,
+            ),
+        }
+        .into()
+    }
+
+    /// erode and then dilate the image, which removes set regions smaller than the square.
+    ///
+    /// - radius - distance from the center to the edge of the square.
+    ///
+    /// returns - the opened image.
+    pub fn open(&self, radius: usize) -> Self {
+        self.erode(radius).dilate(radius)
+    }
+
+    /// dilate and then erode the image, which fills unset holes smaller than the square.
+    ///
+    /// - radius - distance from the center to the edge of the square.
+    ///
+    /// returns - the closed image.
+    pub fn close(&self, radius: usize) -> Self {
+        self.dilate(radius).erode(radius)
+    }
+
+    /// find the distance from every pixel to the nearest set pixel.
+    ///
+    /// distances are approximated with a 3-4 chamfer, which is within about 8% of the euclidean
+    /// distance. set pixels have a distance of 0.
+    ///
+    /// returns - distances in pixels, with max_val as the largest. every pixel is 65535 if none are set.
+    pub fn distance_transform(&self) -> NetPBM<NetPGMFile> {
+        let (width, height) = (self.class.width, self.class.height);
+        let unreachable = u32::MAX / 2;
+        let mut distances: Vec<Vec<u32>> = self
+            .class
+            .pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|x| if *x { 0 } else { unreachable })
+                    .collect()
+            })
+            .collect();
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut d = distances[y][x];
+                if x > 0 {
+                    d = d.min(distances[y][x - 1] + 3);
+                }
+                if y > 0 {
+                    d = d.min(distances[y - 1][x] + 3);
+                    if x > 0 {
+                        d = d.min(distances[y - 1][x - 1] + 4);
+                    }
+                    if x + 1 < width {
+                        d = d.min(distances[y - 1][x + 1] + 4);
+                    }
+                }
+                distances[y][x] = d;
+            }
+        }
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                let mut d = distances[y][x];
+                if x + 1 < width {
+                    d = d.min(distances[y][x + 1] + 3);
+                }
+                if y + 1 < height {
+                    d = d.min(distances[y + 1][x] + 3);
+                    if x + 1 < width {
+                        d = d.min(distances[y + 1][x + 1] + 4);
+                    }
+                    if x > 0 {
+                        d = d.min(distances[y + 1][x - 1] + 4);
+                    }
+                }
+                distances[y][x] = d;
+            }
+        }
+
+        let pixels: Vec<Vec<u16>> = distances
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|d| ((d + 1) / 3).min(u16::MAX as u32) as u16)
+                    .collect()
+            })
+            .collect();
+        let max_val = pixels.iter().flatten().copied().max().unwrap_or(0).max(1);
+        NetPGMFile {
+            width,
+            height,
+            max_val,
+            pixels,
+        }
+        .into()
+    }
+
+    /// hash every row of pixels, to find which rows differ between images.
+    ///
+    /// returns - a stable FNV-1a hash of each row, top to bottom.
+    pub fn row_hashes(&self) -> Vec<u64> {
+        self.class
+            .pixels
+            .iter()
+            .map(|row| fnv1a(row.iter().map(|x| u8::from(*x))))
+            .collect()
+    }
+
+    /// hash the whole image, for caching work done on it.
+    ///
+    /// returns - a stable FNV-1a hash of the size and every pixel.
+    pub fn content_hash(&self) -> u64 {
+        let header = format!("P1 {} {}\n", self.class.width, self.class.height);
+        fnv1a(
+            header
+                .bytes()
+                .chain(self.class.pixels.iter().flatten().map(|x| u8::from(*x))),
+        )
+    }
+
+    /// shift the image, wrapping pixels that leave one edge around to the opposite edge.
+    ///
+    /// - dx - pixels to shift right. negative shifts left.
+    /// - dy - pixels to shift down. negative shifts up.
+    pub fn roll(&mut self, dx: isize, dy: isize) {
+        roll_pixels(&mut self.class.pixels, dx, dy);
+    }
+
+    /// check that saving the image in binary and loading it again gives back the same pixels.
+    ///
+    /// returns - true if the loaded image is equal to this one.
+    pub fn roundtrips_raw(&self) -> bool {
+        parse_pbm(&self.to_raw()).is_ok_and(|image| image.class == self.class)
+    }
+
+    /// compress the image with run-length encoding, as used for fax-style storage.
+    /// each row is written as the lengths of its runs of pixels, alternating white and black,
+    /// starting with white. a row that starts with black begins with a white run of 0.
+    /// lengths are LEB128 varints: 7 bits per byte, low bits first, high bit set if more follow.
+    ///
+    /// returns - the encoded rows, one after another. the size is not included.
+    pub fn to_rle(&self) -> Vec<u8> {
+        let mut rle = Vec::new();
+        for row in &self.class.pixels {
+            let mut color = false;
+            let mut x = 0;
+            while x < row.len() {
+                let run = row[x..].iter().take_while(|&&pixel| pixel == color).count();
+                let mut length = run;
+                while length >= 0x80 {
+                    rle.push((length & 0x7f) as u8 | 0x80);
+                    length >>= 7;
+                }
+                rle.push(length as u8);
+                x += run;
+                color = !color;
+            }
+        }
+        rle
+    }
+
+    /// decompress an image written by to_rle.
+    ///
+    /// - width  - width of the image.
+    /// - height - height of the image.
+    /// - rle    - the encoded rows.
+    ///
+    /// returns - the image, or an error if the runs don't add up to the size of the image.
+    pub fn from_rle(width: usize, height: usize, rle: &[u8]) -> Result<Self, PbmError> {
+        let mut offset = 0;
+        let mut pixels = Vec::with_capacity(height);
+        for y in 0..height {
+            let mut row = Vec::with_capacity(width);
+            let mut color = false;
+            while row.len() < width {
+                let start = offset;
+                let mut run = 0usize;
+                let mut shift = 0;
+                loop {
+                    let byte = *rle.get(offset).ok_or(PbmError::UnexpectedEof)?;
+                    offset += 1;
+                    run |= usize::from(byte & 0x7f)
+                        .checked_shl(shift)
+                        .filter(|bits| bits >> shift == usize::from(byte & 0x7f))
+                        .ok_or(PbmError::InvalidSample {
+                            offset: start,
+                            pixel: y * width + row.len(),
+                        })?;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                    shift += 7;
+                }
+                if run > width - row.len() {
+                    return Err(PbmError::InvalidSample {
+                        offset: start,
+                        pixel: y * width + row.len(),
+                    });
+                }
+                row.resize(row.len() + run, color);
+                color = !color;
+            }
+            pixels.push(row);
+        }
+        if offset != rle.len() {
+            return Err(PbmError::TooManyPixels);
+        }
+
+        Ok(NetPBMFile {
+            width,
+            height,
+            pixels,
+        }
+        .into())
+    }
+
+    /// draw evenly spaced horizontal and vertical lines, starting at the top left corner.
+    ///
+    /// - spacing - distance between lines. does nothing if 0.
+    /// - value   - value of the lines. false is white, true is black.
+    pub fn draw_grid(&mut self, spacing: usize, value: bool) {
+        grid_pixels(&mut self.class.pixels, spacing, value);
+    }
+
+    /// iterate over the pixels in a rectangle, row by row. an empty rectangle yields nothing.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - width  - width of the rectangle.
+    /// - height - height of the rectangle.
+    ///
+    /// returns - (x, y, value) for each pixel, or an error if the rectangle doesn't fit.
+    pub fn region_pixels(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<impl Iterator<Item = (usize, usize, bool)>, PbmError> {
+        check_region(self.class.width, self.class.height, x, y, width, height)?;
+        Ok(
+            region_iter(&self.class.pixels, x, y, width, height)
+                .map(|(x, y, pixel)| (x, y, *pixel)),
+        )
+    }
+
+    /// make lines 4-connected, so every set pixel reaches its neighbours through edges, not corners.
+    /// wherever two set pixels only touch diagonally, the pixel beside the upper one is set,
+    /// turning the diagonal step into a staircase.
+    ///
+    /// returns - a copy of the image with the staircase pixels added.
+    pub fn to_4connected(&self) -> NetPBM<NetPBMFile> {
+        let (width, height) = (self.class.width, self.class.height);
+        let pixels = &self.class.pixels;
+        let mut connected = self.class.pixels.clone();
+
+        for y in 0..height.saturating_sub(1) {
+            for x in 0..width {
+                if !pixels[y][x] || pixels[y + 1][x] {
+                    continue;
+                }
+                if x + 1 < width && pixels[y + 1][x + 1] && !pixels[y][x + 1] {
+                    connected[y][x + 1] = true;
+                }
+                if x > 0 && pixels[y + 1][x - 1] && !pixels[y][x - 1] {
+                    connected[y][x - 1] = true;
+                }
+            }
+        }
+
+        NetPBMFile {
+            width,
+            height,
+            pixels: connected,
+        }
+        .into()
+    }
+}
+
+impl NetPBM<NetPGMFile> {
+    /// create a new PGM File.
+    ///
+    /// - width      - immutable size for image width.
+    /// - height     - immutable size for image height.
+    pub fn new_pgm(width: usize, height: usize, max_val: u16) -> Self {
+        let class = NetPGMFile {
+            width,
+            height,
+            max_val,
+            pixels: vec![vec![0; width]; height],
+        };
+        Self {
+            class,
+            source_format: None,
+            comment: None,
+        }
+    }
+
+    /// create a PGM File from a function of pixel positions.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a pixel.
+    /// - f       - called with (x, y) for every pixel, returning its value. clamped to max_val.
+    pub fn from_fn(
+        width: usize,
+        height: usize,
+        max_val: u16,
+        f: impl Fn(usize, usize) -> u16,
+    ) -> Self {
+        let mut pgm = Self::new_pgm(width, height, max_val);
+        for (y, row) in pgm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = f(x, y).min(max_val);
+            }
+        }
+        pgm
+    }
+
+    /// create a checkerboard PGM File.
+    ///
+    /// - width       - immutable size for image width.
+    /// - height      - immutable size for image height.
+    /// - max_val     - maximum value of a pixel.
+    /// - square_size - width and height of each square. treated as 1 if 0.
+    /// - value_a     - value of the top left square. clamped to max_val.
+    /// - value_b     - value of the squares next to it. clamped to max_val.
+    pub fn checkerboard(
+        width: usize,
+        height: usize,
+        max_val: u16,
+        square_size: usize,
+        value_a: u16,
+        value_b: u16,
+    ) -> Self {
+        let square_size = square_size.max(1);
+        let mut pgm = Self::new_pgm(width, height, max_val);
+        for (y, row) in pgm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = if (x / square_size + y / square_size).is_multiple_of(2) {
+                    value_a.min(max_val)
+                } else {
+                    value_b.min(max_val)
+                };
+            }
+        }
+        pgm
+    }
+
+    /// create a PGM File with a horizontal gradient.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a pixel.
+    ///
+    /// returns - a gradient from black on the left to white on the right.
+    pub fn gradient(width: usize, height: usize, max_val: u16) -> Self {
+        let mut pgm = Self::new_pgm(width, height, max_val);
+        for row in pgm.class.pixels.iter_mut() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = gradient_value(x, width, max_val);
+            }
+        }
+        pgm
+    }
+
+    /// set a pixels value.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - value - value of pixel. 0 is black, max_val is white. does nothing if above max_val.
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: u16) {
+        if x < self.class.width && y < self.class.height && value <= self.class.max_val {
+            self.class.pixels[y][x] = value;
+        }
+    }
+
+    /// set a pixels value at signed coordinates.
+    ///
+    /// - x     - x position of pixel. does nothing if negative or not in image.
+    /// - y     - y position of pixel. does nothing if negative or not in image.
+    /// - value - value of pixel. 0 is black, max_val is white. does nothing if above max_val.
+    pub fn set_pixel_signed(&mut self, x: i32, y: i32, value: u16) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.set_pixel(x, y, value);
+        }
+    }
+
+    /// set a pixels value, saturating at max_val.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - value - value of pixel. 0 is black, max_val is white. clamped to max_val.
+    pub fn set_pixel_clamped(&mut self, x: usize, y: usize, value: u16) {
+        self.set_pixel(x, y, value.min(self.class.max_val));
+    }
+
+    /// get a pixels value.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - value of pixel. 0 is black, max_val is white.
+    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<u16> {
+        if x < self.class.width && y < self.class.height {
+            return Some(self.class.pixels[y][x]);
+        }
+        None
+    }
+
+    /// swap the x and y axes of the image.
+    ///
+    /// returns - a new image where the pixel at (x, y) is the original pixel at (y, x).
+    pub fn transpose(&self) -> Self {
+        let class = NetPGMFile {
+            width: self.class.height,
+            height: self.class.width,
+            max_val: self.class.max_val,
+            pixels: transpose_pixels(&self.class.pixels, self.class.width),
+        };
+        class.into()
+    }
+
+    /// change the maximum value of the image, rescaling every sample to match.
+    ///
+    /// - max_val - new maximum value of a pixel.
+    pub fn rescale_max_val(&mut self, max_val: u16) {
+        let old_max_val = self.class.max_val;
+        for sample in self.class.pixels.iter_mut().flatten() {
+            *sample = rescale_sample(*sample, old_max_val, max_val);
+        }
+        self.class.max_val = max_val;
+    }
+
+    /// copy the image with a new maximum value, rescaling every sample to match.
+    ///
+    /// - max_val - new maximum value of a pixel.
+    ///
+    /// returns - the rescaled copy. the original is unchanged.
+    pub fn with_max_val(&self, max_val: u16) -> Self {
+        let mut image = self.clone();
+        image.rescale_max_val(max_val);
+        image
+    }
+
+    /// rescale the image to a maximum value of 255.
+    ///
+    /// returns - the image with one byte per pixel.
+    pub fn into_8bit(mut self) -> Self {
+        self.rescale_max_val(255);
+        self
+    }
+
+    /// rescale the image to a maximum value of 65535.
+    ///
+    /// returns - the image with two bytes per pixel.
+    pub fn into_16bit(mut self) -> Self {
+        self.rescale_max_val(65535);
+        self
+    }
+
+    /// crop a rectangle out of the image and resize it with bilinear sampling.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - w      - width of the rectangle.
+    /// - h      - height of the rectangle.
+    /// - out_w  - width of the output image.
+    /// - out_h  - height of the output image.
+    ///
+    /// returns - the resized patch, or an error if the rectangle is not inside the image.
+    #[cfg(feature = "std")]
+    pub fn extract_patch(
+        &self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        out_w: usize,
+        out_h: usize,
+    ) -> Result<Self, PbmError> {
+        check_rect(self.class.width, self.class.height, x, y, w, h)?;
+
+        let mut patch = Self::new_pgm(out_w, out_h, self.class.max_val);
+        for (out_y, row) in patch.class.pixels.iter_mut().enumerate() {
+            for (out_x, pixel) in row.iter_mut().enumerate() {
+                let sx = (out_x as f64 + 0.5) * w as f64 / out_w as f64 - 0.5;
+                let sy = (out_y as f64 + 0.5) * h as f64 / out_h as f64 - 0.5;
+                let sampled = sample_bilinear_clamped(
+                    |px, py| [self.class.pixels[y + py][x + px]],
+                    sx,
+                    sy,
+                    w,
+                    h,
+                );
+                *pixel = sampled[0];
+            }
+        }
+        Ok(patch)
+    }
+
+    /// get every pixel divided by max_val.
+    ///
+    /// returns - pixels from 0.0 to 1.0, in row-major order.
+    pub fn samples_normalized(&self) -> Vec<f32> {
+        let max_val = self.class.max_val as f32;
+        self.class
+            .pixels
+            .iter()
+            .flatten()
+            .map(|x| {
+                if max_val > 0.0 {
+                    *x as f32 / max_val
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// create a new PGM File from normalized pixels.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a pixel.
+    /// - samples - pixels from 0.0 to 1.0, in row-major order. clamped, and missing pixels are black.
+    #[cfg(feature = "std")]
+    pub fn from_normalized(width: usize, height: usize, max_val: u16, samples: &[f32]) -> Self {
+        let mut pgm = Self::new_pgm(width, height, max_val);
+        for (pixel, sample) in pgm.class.pixels.iter_mut().flatten().zip(samples) {
+            *pixel = denormalize_sample(*sample, max_val);
+        }
+        pgm
+    }
+
+    /// replace every pixel with the result of a function. results are clamped to max_val.
+    ///
+    /// - f - function from the old pixel to the new pixel.
+    pub fn map_pixels<F: Fn(u16) -> u16>(&mut self, f: F) {
+        let max_val = self.class.max_val;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            *pixel = f(*pixel).min(max_val);
+        }
+    }
+
+    /// replace every pixel with the result of a function, processing rows in parallel.
+    /// results are clamped to max_val.
+    ///
+    /// - f - function from the old pixel to the new pixel.
+    #[cfg(feature = "rayon")]
+    pub fn par_map_pixels<F: Fn(u16) -> u16 + Sync>(&mut self, f: F) {
+        use rayon::prelude::*;
+
+        let max_val = self.class.max_val;
+        self.class.pixels.par_iter_mut().for_each(|row| {
+            for pixel in row.iter_mut() {
+                *pixel = f(*pixel).min(max_val);
+            }
+        });
+    }
+
+    /// replace a whole row of pixels.
+    ///
+    /// - y   - y position of the row.
+    /// - row - values of the pixels, left to right. must be as long as the image is wide, and no value can be larger than max_val.
+    pub fn set_row(&mut self, y: usize, row: &[u16]) -> Result<(), PbmError> {
+        check_row(self.class.width, self.class.height, y, row.len())?;
+        for value in row {
+            check_sample(*value, self.class.max_val)?;
+        }
+        self.class.pixels[y].copy_from_slice(row);
+        Ok(())
+    }
+
+    /// get a whole row of pixels.
+    ///
+    /// - y - y position of the row.
+    ///
+    /// returns - values of the pixels, left to right, or None if the row is not in the image.
+    pub fn get_row(&self, y: usize) -> Option<&[u16]> {
+        self.class.pixels.get(y).map(Vec::as_slice)
+    }
+
+    /// write pixels one after another, left to right and top to bottom.
+    ///
+    /// returns - a writer starting at the top left pixel.
+    pub fn writer(&mut self) -> PixelWriter<'_, u16> {
+        PixelWriter {
+            pixels: &mut self.class.pixels,
+            width: self.class.width,
+            max_val: self.class.max_val,
+            check: |value, max_val| check_sample(*value, max_val),
+            position: 0,
+        }
+    }
+
+    /// replace the region of pixels connected to (x, y) that match its value.
+    ///
+    /// pixels are connected through their left, right, top and bottom neighbours.
+    ///
+    /// - x     - x position of the starting pixel. does nothing if not in image.
+    /// - y     - y position of the starting pixel. does nothing if not in image.
+    /// - value - new value of the region. does nothing if above max_val.
+    pub fn flood_fill(&mut self, x: usize, y: usize, value: u16) {
+        if value <= self.class.max_val {
+            flood_fill_pixels(&mut self.class.pixels, x, y, value);
+        }
+    }
+
+    /// get the smallest max_val that can hold every pixel without losing data.
+    ///
+    /// returns - the largest pixel in the image, or 0 if the image is empty.
+    pub fn min_max_val(&self) -> u16 {
+        self.class
+            .pixels
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// count how many different values the pixels have.
+    ///
+    /// returns - the number of distinct gray levels in the image.
+    pub fn distinct_levels(&self) -> usize {
+        let mut seen = vec![false; usize::from(u16::MAX) + 1];
+        for &pixel in self.class.pixels.iter().flatten() {
+            seen[usize::from(pixel)] = true;
+        }
+        seen.iter().filter(|&&seen| seen).count()
+    }
+
+    /// combine this image with another one, sample by sample.
+    ///
+    /// - other - image with the same size and max_val.
+    /// - mode  - how to combine the samples. results are clamped to max_val.
+    ///
+    /// returns - the combined image, or an error if the images don't match.
+    pub fn blend(&self, other: &Self, mode: BlendMode) -> Result<Self, PbmError> {
+        check_same_shape(
+            (self.class.width, self.class.height, self.class.max_val),
+            (other.class.width, other.class.height, other.class.max_val),
+        )?;
+
+        let max_val = self.class.max_val;
+        let mut blended = Self::new_pgm(self.class.width, self.class.height, max_val);
+        for ((out, a), b) in blended
+            .class
+            .pixels
+            .iter_mut()
+            .flatten()
+            .zip(self.class.pixels.iter().flatten())
+            .zip(other.class.pixels.iter().flatten())
+        {
+            *out = blend_sample(*a, *b, max_val, mode);
+        }
+        Ok(blended)
+    }
+
+    /// spread the pixel values so their histogram is roughly flat from 0 to max_val.
+    ///
+    /// does nothing if every pixel has the same value.
+    pub fn equalize(&mut self) {
+        let mut histogram = vec![0u64; self.class.max_val as usize + 1];
+        for pixel in self.class.pixels.iter().flatten() {
+            histogram[*pixel as usize] += 1;
+        }
+
+        let mut cdf = histogram;
+        for i in 1..cdf.len() {
+            cdf[i] += cdf[i - 1];
+        }
+        let total = cdf[cdf.len() - 1];
+        let cdf_min = cdf.iter().copied().find(|count| *count > 0).unwrap_or(0);
+        if total == cdf_min {
+            return;
+        }
+
+        let range = total - cdf_min;
+        let max_val = self.class.max_val as u64;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            let count = cdf[*pixel as usize] - cdf_min;
+            *pixel = ((count * max_val + range / 2) / range) as u16;
+        }
+    }
+
+    /// blur the image with a gaussian kernel.
+    ///
+    /// - sigma - standard deviation of the kernel in pixels. must be larger than 0.
+    #[cfg(feature = "std")]
+    pub fn gaussian_blur(&mut self, sigma: f64) -> Result<(), PbmError> {
+        let kernel = gaussian_kernel(sigma)?;
+        let plane: Vec<f64> = self
+            .class
+            .pixels
+            .iter()
+            .flatten()
+            .map(|x| *x as f64)
+            .collect();
+        let blurred = convolve_plane(&plane, self.class.width, self.class.height, &kernel);
+        for (pixel, value) in self.class.pixels.iter_mut().flatten().zip(blurred) {
+            *pixel = value.round().clamp(0.0, self.class.max_val as f64) as u16;
+        }
+        Ok(())
+    }
+
+    /// sharpen the image by adding the difference between it and a blurred copy.
+    ///
+    /// - sigma  - standard deviation of the blur in pixels. must be larger than 0.
+    /// - amount - how much of the difference to add. 0 leaves the image unchanged.
+    #[cfg(feature = "std")]
+    pub fn unsharp_mask(&mut self, sigma: f64, amount: f64) -> Result<(), PbmError> {
+        let kernel = gaussian_kernel(sigma)?;
+        let plane: Vec<f64> = self
+            .class
+            .pixels
+            .iter()
+            .flatten()
+            .map(|x| *x as f64)
+            .collect();
+        let blurred = convolve_plane(&plane, self.class.width, self.class.height, &kernel);
+        for (pixel, value) in self.class.pixels.iter_mut().flatten().zip(blurred) {
+            let original = *pixel as f64;
+            let sharpened = original + amount * (original - value);
+            *pixel = sharpened.round().clamp(0.0, self.class.max_val as f64) as u16;
+        }
+        Ok(())
+    }
+
+    /// rotate the image counter-clockwise around its center, with bilinear sampling.
+    ///
+    /// the image grows to fit the rotated corners.
+    ///
+    /// - degrees - angle to rotate by.
+    /// - fill    - value of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the rotated image.
+    #[cfg(feature = "std")]
+    pub fn rotate(&self, degrees: f64, fill: u16) -> Self {
+        let (width, height) = (self.class.width, self.class.height);
+        let (out_width, out_height, map) = rotated_bounds(width, height, degrees);
+        let pixels = warp_pixels(
+            |x, y| [self.class.pixels[y][x]],
+            width,
+            height,
+            out_width,
+            out_height,
+            map,
+            [fill.min(self.class.max_val)],
+        );
+        NetPGMFile {
+            width: out_width,
+            height: out_height,
+            max_val: self.class.max_val,
+            pixels: pixels
+                .into_iter()
+                .map(|row| row.into_iter().map(|[value]| value).collect())
+                .collect(),
+        }
+        .into()
+    }
+
+    /// shear the image, with nearest neighbour sampling.
+    ///
+    /// a pixel at (x, y) moves to (x + shear_x * y, y + shear_y * x), and the image grows to fit.
+    /// if shear_x * shear_y is 1 the image collapses to a line, and every pixel is fill.
+    ///
+    /// - shear_x - horizontal shift per row.
+    /// - shear_y - vertical shift per column.
+    /// - fill    - value of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the sheared image.
+    #[cfg(feature = "std")]
+    pub fn shear(&self, shear_x: f64, shear_y: f64, fill: u16) -> Self {
+        let (width, height, pixels) = shear_pixels(
+            &self.class.pixels,
+            self.class.width,
+            self.class.height,
+            shear_x,
+            shear_y,
+            fill.min(self.class.max_val),
+        );
+        NetPGMFile {
+            width,
+            height,
+            max_val: self.class.max_val,
+            pixels,
+        }
+        .into()
+    }
+
+    /// transform the image with an affine matrix, with bilinear sampling.
+    ///
+    /// a position (x, y) in the image moves to (a * x + b * y + c, d * x + e * y + f).
+    ///
+    /// - matrix - the rows [a, b, c] and [d, e, f]. a singular matrix gives an image of fill.
+    /// - out_w  - width of the output image.
+    /// - out_h  - height of the output image.
+    /// - fill   - value of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the transformed image.
+    #[cfg(feature = "std")]
+    pub fn warp_affine(
+        &self,
+        matrix: [[f64; 3]; 2],
+        out_w: usize,
+        out_h: usize,
+        fill: u16,
+    ) -> Self {
+        let pixels = warp_pixels(
+            |x, y| [self.class.pixels[y][x]],
+            self.class.width,
+            self.class.height,
+            out_w,
+            out_h,
+            inverse_affine_map(matrix),
+            [fill.min(self.class.max_val)],
+        );
+        NetPGMFile {
+            width: out_w,
+            height: out_h,
+            max_val: self.class.max_val,
+            pixels: pixels
+                .into_iter()
+                .map(|row| row.into_iter().map(|[value]| value).collect())
+                .collect(),
+        }
+        .into()
+    }
+
+    /// check that the image is internally consistent.
+    ///
+    /// returns - an error if the pixels don't match the size of the image or exceed max_val.
+    pub fn validate(&self) -> Result<(), PbmError> {
+        check_grid(&self.class.pixels, self.class.width, self.class.height)?;
+        for value in self.class.pixels.iter().flatten() {
+            check_sample(*value, self.class.max_val)?;
+        }
+        Ok(())
+    }
+
+    /// shrink the image by averaging square blocks of pixels.
+    ///
+    /// - factor - width and height of each block. blocks on the edges average only the pixels they cover.
+    ///
+    /// returns - the smaller image, or an error if factor is 0.
+    pub fn downsample(&self, factor: usize) -> Result<Self, PbmError> {
+        if factor == 0 {
+            return Err(PbmError::ZeroFactor);
+        }
+        let pixels = downsample_pixels(
+            |x, y| [self.class.pixels[y][x]],
+            self.class.width,
+            self.class.height,
+            factor,
+        );
+        Ok(NetPGMFile {
+            width: self.class.width.div_ceil(factor),
+            height: self.class.height.div_ceil(factor),
+            max_val: self.class.max_val,
+            pixels: pixels
+                .into_iter()
+                .map(|row| row.into_iter().map(|[value]| value).collect())
+                .collect(),
+        }
+        .into())
+    }
+
+    /// repeatedly halve the image by averaging 2x2 blocks, until it is 1x1.
+    ///
+    /// returns - every level, starting with a copy of the original image.
+    pub fn mipmap_pyramid(&self) -> Vec<Self> {
+        let mut levels = vec![self.clone()];
+        while let Some(level) = levels.last()
+            && (level.class.width > 1 || level.class.height > 1)
+        {
+            let next = level.downsample(2).unwrap();
+            levels.push(next);
+        }
+        levels
+    }
+
+    /// convert the image to black and white with two thresholds.
+    ///
+    /// pixels at or above high are set. pixels at or above low are set only if they connect to
+    /// a set pixel through their 8 neighbours, and every other pixel is unset.
+    ///
+    /// - low  - smallest value a pixel can have and still be set.
+    /// - high - smallest value that sets a pixel on its own.
+    ///
+    /// returns - a PBM image, where true is a set pixel.
+    pub fn hysteresis_threshold(&self, low: u16, high: u16) -> NetPBM<NetPBMFile> {
+        let (width, height) = (self.class.width, self.class.height);
+        let mut pbm = NetPBM::new_pbm(width, height);
+
+        let mut stack = Vec::new();
+        for (y, row) in self.class.pixels.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                if *value >= high {
+                    stack.push((x, y));
+                }
+            }
+        }
+
+        while let Some((x, y)) = stack.pop() {
+            if pbm.class.pixels[y][x] {
+                continue;
+            }
+            pbm.class.pixels[y][x] = true;
+
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    if !pbm.class.pixels[ny][nx] && self.class.pixels[ny][nx] >= low {
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+        pbm
+    }
+
+    /// draw an anti-aliased line with Xiaolin Wu's algorithm.
+    ///
+    /// each pixel the line touches moves towards value by how much of it the line covers.
+    /// nothing is drawn if a coordinate is NaN or infinite.
+    ///
+    /// - x0    - x position of the start of the line.
+    /// - y0    - y position of the start of the line.
+    /// - x1    - x position of the end of the line.
+    /// - y1    - y position of the end of the line.
+    /// - value - value of the line. clamped to max_val. pixels outside the image are skipped.
+    #[cfg(feature = "std")]
+    pub fn draw_line_aa(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, value: u16) {
+        if ![x0, y0, x1, y1].iter().all(|c| c.is_finite()) {
+            return;
+        }
+        let value = value.min(self.class.max_val) as f64;
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        let (mut x0, mut y0, mut x1, mut y1) = if steep {
+            (y0, x0, y1, x1)
+        } else {
+            (x0, y0, x1, y1)
+        };
+        if x0 > x1 {
+            (x0, x1) = (x1, x0);
+            (y0, y1) = (y1, y0);
+        }
+
+        let mut plot = |x: f64, y: f64, coverage: f64| {
+            let (x, y) = if steep { (y, x) } else { (x, y) };
+            if x < 0.0 || y < 0.0 {
+                return;
+            }
+            let (x, y) = (x as usize, y as usize);
+            if let Some(pixel) = self.class.pixels.get_mut(y).and_then(|row| row.get_mut(x)) {
+                let current = *pixel as f64;
+                *pixel = (current + (value - current) * coverage).round() as u16;
+            }
+        };
+
+        let fpart = |x: f64| x - x.floor();
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        let x_end = x0.round();
+        let y_end = y0 + gradient * (x_end - x0);
+        let x_gap = 1.0 - fpart(x0 + 0.5);
+        let x_start = x_end;
+        plot(x_start, y_end.floor(), (1.0 - fpart(y_end)) * x_gap);
+        plot(x_start, y_end.floor() + 1.0, fpart(y_end) * x_gap);
+        let y_start = y_end;
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let x_gap = fpart(x1 + 0.5);
+        let x_stop = x_end;
+        plot(x_stop, y_end.floor(), (1.0 - fpart(y_end)) * x_gap);
+        plot(x_stop, y_end.floor() + 1.0, fpart(y_end) * x_gap);
+
+        // only walk the part of the major axis inside the image, however long the line is.
+        let limit = if steep {
+            self.class.height
+        } else {
+            self.class.width
+        } as f64;
+        let mut x = (x_start + 1.0).max(0.0);
+        let mut intersect_y = y_start + gradient * (x - x_start);
+        while x < x_stop.min(limit) {
+            plot(x, intersect_y.floor(), 1.0 - fpart(intersect_y));
+            plot(x, intersect_y.floor() + 1.0, fpart(intersect_y));
+            intersect_y += gradient;
+            x += 1.0;
+        }
+    }
+
+    /// hash every row of pixels, to find which rows differ between images.
+    ///
+    /// returns - a stable FNV-1a hash of each row, top to bottom.
+    pub fn row_hashes(&self) -> Vec<u64> {
+        self.class
+            .pixels
+            .iter()
+            .map(|row| fnv1a(row.iter().flat_map(|x| x.to_be_bytes())))
+            .collect()
+    }
+
+    /// hash the whole image, for caching work done on it.
+    ///
+    /// returns - a stable FNV-1a hash of the size, max_val and every pixel.
+    pub fn content_hash(&self) -> u64 {
+        let header = format!(
+            "P2 {} {} {}\n",
+            self.class.width, self.class.height, self.class.max_val
+        );
+        fnv1a(
+            header.bytes().chain(
+                self.class
+                    .pixels
+                    .iter()
+                    .flatten()
+                    .flat_map(|x| x.to_be_bytes()),
+            ),
+        )
+    }
+
+    /// replace every pixel with its entry in a lookup table.
+    ///
+    /// - lut - new value for each value from 0 to max_val. must have max_val + 1 entries, none larger than max_val.
+    pub fn apply_lut(&mut self, lut: &[u16]) -> Result<(), PbmError> {
+        check_lut(lut, self.class.max_val)?;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            *pixel = lut[*pixel as usize];
+        }
+        Ok(())
+    }
+
+    /// shift the image, wrapping pixels that leave one edge around to the opposite edge.
+    ///
+    /// - dx - pixels to shift right. negative shifts left.
+    /// - dy - pixels to shift down. negative shifts up.
+    pub fn roll(&mut self, dx: isize, dy: isize) {
+        roll_pixels(&mut self.class.pixels, dx, dy);
+    }
+
+    /// check that saving the image in binary and loading it again gives back the same pixels.
+    ///
+    /// returns - true if the loaded image is equal to this one.
+    pub fn roundtrips_raw(&self) -> bool {
+        parse_pgm(&self.to_raw(), 0, ParseMode::Strict)
+            .is_ok_and(|(image, _)| image.class == self.class)
+    }
+
+    /// render the image as text, for looking at it in a terminal or log.
+    /// each character is the average of a block of pixels, using the ramp " .:-=+*#%@".
+    /// black pixels become "@" and white pixels become " ".
+    /// the height is halved, since characters are about twice as tall as they are wide.
+    ///
+    /// - cols - width of the text in characters. at most the image width.
+    ///
+    /// returns - one line per row of characters.
+    pub fn to_text_preview(&self, cols: usize) -> String {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        let (width, height) = (self.class.width, self.class.height);
+        let cols = cols.min(width);
+        if cols == 0 || height == 0 {
+            return String::new();
+        }
+        let rows = (height * cols / width / 2).clamp(1, height);
+        let max_val = u64::from(self.class.max_val.max(1));
+
+        let mut text = String::with_capacity((cols + 1) * rows);
+        for row in 0..rows {
+            let (y0, y1) = (row * height / rows, (row + 1) * height / rows);
+            for col in 0..cols {
+                let (x0, x1) = (col * width / cols, (col + 1) * width / cols);
+                let sum: u64 = self.class.pixels[y0..y1]
+                    .iter()
+                    .flat_map(|pixels| &pixels[x0..x1])
+                    .map(|&pixel| u64::from(pixel).min(max_val))
+                    .sum();
+                let mean = sum / ((y1 - y0) * (x1 - x0)) as u64;
+                let darkness = (max_val - mean) * (RAMP.len() as u64 - 1) / max_val;
+                text.push(RAMP[darkness as usize] as char);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// remove the rows and columns around the edge that are all close to one value.
+    ///
+    /// - value     - value of the border.
+    /// - tolerance - how far a sample can be from the border and still count as border.
+    ///
+    /// returns - the trimmed image, or a 0x0 image if every pixel is border.
+    pub fn trim_border(&self, value: u16, tolerance: u16) -> Self {
+        let (x, y, width, height) = content_bounds(&self.class.pixels, |&pixel| {
+            pixel.abs_diff(value) <= tolerance
+        })
+        .unwrap_or_default();
+        NetPGMFile {
+            width,
+            height,
+            max_val: self.class.max_val,
+            pixels: crop_pixels(&self.class.pixels, x, y, width, height),
+        }
+        .into()
+    }
+
+    /// lower max_val as far as possible without losing any data.
+    /// finds the greatest common divisor of max_val and every pixel, then divides them all by it.
+    /// a 16-bit image scaled up from 8 bits has every pixel a multiple of 257, so it packs to 255.
+    ///
+    /// returns - the packed image, or a copy if no divisor larger than 1 is shared.
+    pub fn try_pack(&self) -> NetPBM<NetPGMFile> {
+        let divisor = self
+            .class
+            .pixels
+            .iter()
+            .flatten()
+            .fold(self.class.max_val, |divisor, &pixel| gcd(divisor, pixel));
+        if divisor <= 1 {
+            return self.clone();
+        }
+
+        NetPGMFile {
+            width: self.class.width,
+            height: self.class.height,
+            max_val: self.class.max_val / divisor,
+            pixels: self
+                .class
+                .pixels
+                .iter()
+                .map(|row| row.iter().map(|pixel| pixel / divisor).collect())
+                .collect(),
+        }
+        .into()
+    }
+
+    /// get the shannon entropy of the pixel values, to estimate how well the image compresses.
+    ///
+    /// returns - bits per pixel, from 0.0 for a single value up to log2(max_val + 1).
+    #[cfg(feature = "std")]
+    pub fn entropy(&self) -> f64 {
+        let mut counts = vec![0usize; usize::from(u16::MAX) + 1];
+        for &pixel in self.class.pixels.iter().flatten() {
+            counts[usize::from(pixel)] += 1;
+        }
+
+        let total = (self.class.width * self.class.height) as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+        counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                p * (1.0 / p).log2()
+            })
+            .sum()
+    }
+
+    /// draw evenly spaced horizontal and vertical lines, starting at the top left corner.
+    ///
+    /// - spacing - distance between lines. does nothing if 0.
+    /// - value   - value of the lines. does nothing if above max_val.
+    pub fn draw_grid(&mut self, spacing: usize, value: u16) {
+        if value <= self.class.max_val {
+            grid_pixels(&mut self.class.pixels, spacing, value);
+        }
+    }
+
+    /// replace every pixel close to one value with another.
+    ///
+    /// - from      - value to replace.
+    /// - to        - value to replace it with. clamped to max_val.
+    /// - tolerance - how far a pixel can be from from and still be replaced.
+    pub fn replace_color(&mut self, from: u16, to: u16, tolerance: u16) {
+        let to = to.min(self.class.max_val);
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            if pixel.abs_diff(from) <= tolerance {
+                *pixel = to;
+            }
+        }
+    }
+
+    /// read the image at a fractional position, interpolating between the four nearest pixels.
+    ///
+    /// - x - x position, from 0.0 to width - 1.
+    /// - y - y position, from 0.0 to height - 1.
+    ///
+    /// returns - the interpolated value, or None outside the image instead of clamping.
+    #[cfg(feature = "std")]
+    pub fn sample_bilinear(&self, x: f64, y: f64) -> Option<u16> {
+        let (width, height) = (self.class.width, self.class.height);
+        let inside = |v: f64, len: usize| len > 0 && (0.0..=(len - 1) as f64).contains(&v);
+        if !(inside(x, width) && inside(y, height)) {
+            return None;
+        }
+        let sampled =
+            sample_bilinear_clamped(|x, y| [self.class.pixels[y][x]], x, y, width, height);
+        Some(sampled[0])
+    }
+
+    /// pick a threshold that splits the pixels into dark and light with otsu's method.
+    /// the threshold maximizes the variance between the two groups.
+    /// if several thresholds do, the middle one is picked.
+    ///
+    /// returns - the smallest value in the light group, or 0 if there are fewer than two distinct values.
+    pub fn otsu_threshold(&self) -> u16 {
+        let max_val = self.class.max_val;
+        let mut histogram = vec![0u64; usize::from(max_val) + 1];
+        for &pixel in self.class.pixels.iter().flatten() {
+            histogram[usize::from(pixel.min(max_val))] += 1;
+        }
+
+        let total: u64 = histogram.iter().sum();
+        let total_sum: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| value as f64 * count as f64)
+            .sum();
+
+        let (mut dark, mut dark_sum) = (0u64, 0.0);
+        let mut best = 0.0;
+        let (mut first, mut last) = (0, 0);
+        for threshold in 1..=max_val {
+            let count = histogram[usize::from(threshold) - 1];
+            dark += count;
+            dark_sum += f64::from(threshold - 1) * count as f64;
+            let light = total - dark;
+            if dark == 0 || light == 0 {
+                continue;
+            }
+
+            let mean_difference = dark_sum / dark as f64 - (total_sum - dark_sum) / light as f64;
+            let variance = dark as f64 * light as f64 * mean_difference * mean_difference;
+            if variance > best {
+                best = variance;
+                (first, last) = (threshold, threshold);
+            } else if variance == best {
+                last = threshold;
+            }
+        }
+        first + (last - first) / 2
+    }
+
+    /// brighten every pixel by a constant, stopping at max_val.
+    ///
+    /// - delta - amount to add to every pixel.
+    pub fn saturating_add(&mut self, delta: u16) {
+        let max_val = self.class.max_val;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            *pixel = pixel.saturating_add(delta).min(max_val);
+        }
+    }
+
+    /// darken every pixel by a constant, stopping at 0.
+    ///
+    /// - delta - amount to subtract from every pixel.
+    pub fn saturating_sub(&mut self, delta: u16) {
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            *pixel = pixel.saturating_sub(delta);
+        }
+    }
+
+    /// iterate over the pixels in a rectangle, row by row. an empty rectangle yields nothing.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - width  - width of the rectangle.
+    /// - height - height of the rectangle.
+    ///
+    /// returns - (x, y, value) for each pixel, or an error if the rectangle doesn't fit.
+    pub fn region_pixels(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<impl Iterator<Item = (usize, usize, u16)>, PbmError> {
+        check_region(self.class.width, self.class.height, x, y, width, height)?;
+        Ok(
+            region_iter(&self.class.pixels, x, y, width, height)
+                .map(|(x, y, pixel)| (x, y, *pixel)),
+        )
+    }
+
+    /// lower every sample above max_val to max_val, so the image is safe to save.
+    pub fn clamp_samples(&mut self) {
+        let max_val = self.class.max_val;
+        for sample in self.class.pixels.iter_mut().flatten() {
+            *sample = (*sample).min(max_val);
+        }
+    }
+}
+
+impl NetPBM<NetPPMFile> {
+    /// create a new PPM File.
+    ///
+    /// - width      - immutable size for image width.
+    /// - height     - immutable size for image height.
+    pub fn new_ppm(width: usize, height: usize, max_val: u16) -> Self {
+        let class = NetPPMFile {
+            width,
+            height,
+            max_val,
+            pixels: vec![vec![[0; 3]; width]; height],
+        };
+        Self {
+            class,
+            source_format: None,
+            comment: None,
+        }
+    }
+
+    /// create a PPM File from a function of pixel positions.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a channel.
+    /// - f       - called with (x, y) for every pixel, returning its color. clamped to max_val.
+    pub fn from_fn(
+        width: usize,
+        height: usize,
+        max_val: u16,
+        f: impl Fn(usize, usize) -> [u16; 3],
+    ) -> Self {
+        let mut ppm = Self::new_ppm(width, height, max_val);
+        for (y, row) in ppm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = f(x, y).map(|c| c.min(max_val));
+            }
+        }
+        ppm
+    }
+
+    /// create a checkerboard PPM File.
+    ///
+    /// - width       - immutable size for image width.
+    /// - height      - immutable size for image height.
+    /// - max_val     - maximum value of a channel.
+    /// - square_size - width and height of each square. treated as 1 if 0.
+    /// - color_a     - color of the top left square. clamped to max_val.
+    /// - color_b     - color of the squares next to it. clamped to max_val.
+    pub fn checkerboard(
+        width: usize,
+        height: usize,
+        max_val: u16,
+        square_size: usize,
+        color_a: [u16; 3],
+        color_b: [u16; 3],
+    ) -> Self {
+        let square_size = square_size.max(1);
+        let mut ppm = Self::new_ppm(width, height, max_val);
+        for (y, row) in ppm.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let color = if (x / square_size + y / square_size).is_multiple_of(2) {
+                    color_a
+                } else {
+                    color_b
+                };
+                *pixel = color.map(|c| c.min(max_val));
+            }
+        }
+        ppm
+    }
+
+    /// create a PPM File where each pixel encodes its own position, for checking sampling code.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a channel.
+    ///
+    /// returns - an image where each pixel is [x * max_val / width, y * max_val / height, 0].
+    pub fn uv_map(width: usize, height: usize, max_val: u16) -> Self {
+        Self::from_fn(width, height, max_val, |x, y| {
+            [
+                (x as u64 * max_val as u64 / width as u64) as u16,
+                (y as u64 * max_val as u64 / height as u64) as u16,
+                0,
+            ]
+        })
+    }
+
+    /// create a PPM File with a horizontal gray gradient.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a channel.
+    ///
+    /// returns - a gradient from black on the left to white on the right.
+    pub fn gradient(width: usize, height: usize, max_val: u16) -> Self {
+        let mut ppm = Self::new_ppm(width, height, max_val);
+        for row in ppm.class.pixels.iter_mut() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = [gradient_value(x, width, max_val); 3];
+            }
+        }
+        ppm
+    }
+
+    /// set a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - color - color of pixel. rgb order. 0 is black, max_val is white. ignored above max_val.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u16; 3]) {
+        if x < self.class.width
+            && y < self.class.height
+            && color.iter().all(|x| x <= &self.class.max_val)
+        {
+            self.class.pixels[y][x] = color;
+        }
+    }
+
+    /// set a pixels color at signed coordinates.
+    ///
+    /// - x     - x position of pixel. does nothing if negative or not in image.
+    /// - y     - y position of pixel. does nothing if negative or not in image.
+    /// - color - color of pixel. rgb order. 0 is black, max_val is white. ignored above max_val.
+    pub fn set_pixel_signed(&mut self, x: i32, y: i32, color: [u16; 3]) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.set_pixel(x, y, color);
+        }
+    }
+
+    /// set a pixels color, saturating each channel at max_val.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - color - color of pixel. rgb order. 0 is black, max_val is white. clamped to max_val.
+    pub fn set_pixel_clamped(&mut self, x: usize, y: usize, color: [u16; 3]) {
+        let max_val = self.class.max_val;
+        self.set_pixel(x, y, color.map(|c| c.min(max_val)));
+    }
+
+    /// get a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - color of pixel. rgb order. 0 is black, max_val is white.
+    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<[u16; 3]> {
+        if x < self.class.width && y < self.class.height {
+            return Some(self.class.pixels[y][x]);
+        }
+        None
+    }
+
+    /// get a pixels color in linear light.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - linear rgb color of pixel, each channel from 0.0 to 1.0.
+    #[cfg(feature = "std")]
+    pub fn pixel_linear(&self, x: usize, y: usize) -> Option<[f64; 3]> {
+        let max_val = f64::from(self.class.max_val.max(1));
+        let color = self.class.pixels.get(y)?.get(x)?;
+        Some(color.map(|c| srgb_to_linear(f64::from(c) / max_val)))
+    }
+
+    /// set a pixels color from linear light.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - color - linear rgb color of pixel. each channel is clamped to 0.0 to 1.0.
+    #[cfg(feature = "std")]
+    pub fn set_pixel_linear(&mut self, x: usize, y: usize, color: [f64; 3]) {
+        let max_val = f64::from(self.class.max_val);
+        let color = color.map(|c| (linear_to_srgb(c.clamp(0.0, 1.0)) * max_val).round() as u16);
+        self.set_pixel(x, y, color);
+    }
+
+    /// swap the x and y axes of the image.
+    ///
+    /// returns - a new image where the pixel at (x, y) is the original pixel at (y, x).
+    pub fn transpose(&self) -> Self {
+        let class = NetPPMFile {
+            width: self.class.height,
+            height: self.class.width,
+            max_val: self.class.max_val,
+            pixels: transpose_pixels(&self.class.pixels, self.class.width),
+        };
+        class.into()
+    }
+
+    /// change the maximum value of the image, rescaling every sample to match.
+    ///
+    /// - max_val - new maximum value of a channel.
+    pub fn rescale_max_val(&mut self, max_val: u16) {
+        let old_max_val = self.class.max_val;
+        for sample in self.class.pixels.iter_mut().flatten().flatten() {
+            *sample = rescale_sample(*sample, old_max_val, max_val);
+        }
+        self.class.max_val = max_val;
+    }
+
+    /// copy the image with a new maximum value, rescaling every sample to match.
+    ///
+    /// - max_val - new maximum value of a channel.
+    ///
+    /// returns - the rescaled copy. the original is unchanged.
+    pub fn with_max_val(&self, max_val: u16) -> Self {
+        let mut image = self.clone();
+        image.rescale_max_val(max_val);
+        image
+    }
+
+    /// rescale the image to a maximum value of 255.
+    ///
+    /// returns - the image with one byte per channel.
+    pub fn into_8bit(mut self) -> Self {
+        self.rescale_max_val(255);
+        self
+    }
+
+    /// rescale the image to a maximum value of 65535.
+    ///
+    /// returns - the image with two bytes per channel.
+    pub fn into_16bit(mut self) -> Self {
+        self.rescale_max_val(65535);
+        self
+    }
+
+    /// crop a rectangle out of the image and resize it with bilinear sampling.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - w      - width of the rectangle.
+    /// - h      - height of the rectangle.
+    /// - out_w  - width of the output image.
+    /// - out_h  - height of the output image.
+    ///
+    /// returns - the resized patch, or an error if the rectangle is not inside the image.
+    #[cfg(feature = "std")]
+    pub fn extract_patch(
+        &self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        out_w: usize,
+        out_h: usize,
+    ) -> Result<Self, PbmError> {
+        check_rect(self.class.width, self.class.height, x, y, w, h)?;
+
+        let mut patch = Self::new_ppm(out_w, out_h, self.class.max_val);
+        for (out_y, row) in patch.class.pixels.iter_mut().enumerate() {
+            for (out_x, pixel) in row.iter_mut().enumerate() {
+                let sx = (out_x as f64 + 0.5) * w as f64 / out_w as f64 - 0.5;
+                let sy = (out_y as f64 + 0.5) * h as f64 / out_h as f64 - 0.5;
+                let sampled = sample_bilinear_clamped(
+                    |px, py| self.class.pixels[y + py][x + px],
+                    sx,
+                    sy,
+                    w,
+                    h,
+                );
+                *pixel = sampled;
+            }
+        }
+        Ok(patch)
+    }
+
+    /// get every channel divided by max_val.
+    ///
+    /// returns - channels from 0.0 to 1.0, in row-major rgb order.
+    pub fn samples_normalized(&self) -> Vec<f32> {
+        let max_val = self.class.max_val as f32;
+        self.class
+            .pixels
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|x| {
+                if max_val > 0.0 {
+                    *x as f32 / max_val
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// create a new PPM File from normalized channels.
+    ///
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a channel.
+    /// - samples - channels from 0.0 to 1.0, in row-major rgb order. clamped, and missing channels are black.
+    #[cfg(feature = "std")]
+    pub fn from_normalized(width: usize, height: usize, max_val: u16, samples: &[f32]) -> Self {
+        let mut ppm = Self::new_ppm(width, height, max_val);
+        for (channel, sample) in ppm.class.pixels.iter_mut().flatten().flatten().zip(samples) {
+            *channel = denormalize_sample(*sample, max_val);
+        }
+        ppm
+    }
+
+    /// split the image into a grid of tiles.
+    ///
+    /// - tile_w - width of each tile. the last column may be narrower.
+    /// - tile_h - height of each tile. the last row may be shorter.
+    ///
+    /// returns - rows of tiles, top to bottom, each left to right. empty if either size is 0.
+    pub fn into_tiles(&self, tile_w: usize, tile_h: usize) -> Vec<Vec<NetPBM<NetPPMFile>>> {
+        if tile_w == 0 || tile_h == 0 {
+            return vec![];
+        }
+
+        (0..self.class.height)
+            .step_by(tile_h)
+            .map(|tile_y| {
+                (0..self.class.width)
+                    .step_by(tile_w)
+                    .map(|tile_x| {
+                        let w = tile_w.min(self.class.width - tile_x);
+                        let h = tile_h.min(self.class.height - tile_y);
+                        let mut tile = Self::new_ppm(w, h, self.class.max_val);
+                        for (y, row) in tile.class.pixels.iter_mut().enumerate() {
+                            row.copy_from_slice(&self.class.pixels[tile_y + y][tile_x..tile_x + w]);
+                        }
+                        tile
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// stitch a grid of tiles back into one image.
+    ///
+    /// every tile in a row must have the same height, every tile in a column must have the
+    /// same width, and every tile must have the same max_val.
+    ///
+    /// - tiles - rows of tiles, top to bottom, each left to right.
+    ///
+    /// returns - the stitched image, or an error describing the first mismatched tile.
+    pub fn from_tiles(tiles: &[Vec<NetPBM<NetPPMFile>>]) -> Result<NetPBM<NetPPMFile>, PbmError> {
+        let first_row = tiles
+            .first()
+            .filter(|row| !row.is_empty())
+            .ok_or(PbmError::NoTiles)?;
+        let max_val = first_row[0].class.max_val;
+        let column_widths: Vec<usize> = first_row.iter().map(|tile| tile.class.width).collect();
+        let row_heights: Vec<usize> = tiles
+            .iter()
+            .map(|row| row.first().map_or(0, |tile| tile.class.height))
+            .collect();
+
+        for (row_id, row) in tiles.iter().enumerate() {
+            if row.len() != column_widths.len() {
+                return Err(PbmError::TileCountMismatch {
+                    row: row_id,
+                    expected: column_widths.len(),
+                    found: row.len(),
+                });
+            }
+            for (column_id, tile) in row.iter().enumerate() {
+                let expected = (column_widths[column_id], row_heights[row_id]);
+                let found = (tile.class.width, tile.class.height);
+                if expected != found {
+                    return Err(PbmError::DimensionMismatch { expected, found });
+                }
+                if tile.class.max_val != max_val {
+                    return Err(PbmError::MaxValMismatch {
+                        expected: max_val,
+                        found: tile.class.max_val,
+                    });
+                }
+            }
+        }
+
+        let mut ppm = Self::new_ppm(
+            column_widths.iter().sum(),
+            row_heights.iter().sum(),
+            max_val,
+        );
+        let mut tile_y = 0;
+        for row in tiles {
+            let mut tile_x = 0;
+            for tile in row {
+                for (y, tile_row) in tile.class.pixels.iter().enumerate() {
+                    ppm.class.pixels[tile_y + y][tile_x..tile_x + tile.class.width]
+                        .copy_from_slice(tile_row);
+                }
+                tile_x += tile.class.width;
+            }
+            tile_y += row[0].class.height;
+        }
+        Ok(ppm)
+    }
+
+    /// replace every color with the result of a function. results are clamped to max_val.
+    ///
+    /// - f - function from the old color to the new color.
+    pub fn map_pixels<F: Fn([u16; 3]) -> [u16; 3]>(&mut self, f: F) {
+        let max_val = self.class.max_val;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            *pixel = f(*pixel).map(|c| c.min(max_val));
+        }
+    }
+
+    /// replace every color with the result of a function, processing rows in parallel.
+    /// results are clamped to max_val.
+    ///
+    /// - f - function from the old color to the new color.
+    #[cfg(feature = "rayon")]
+    pub fn par_map_pixels<F: Fn([u16; 3]) -> [u16; 3] + Sync>(&mut self, f: F) {
+        use rayon::prelude::*;
+
+        let max_val = self.class.max_val;
+        self.class.pixels.par_iter_mut().for_each(|row| {
+            for pixel in row.iter_mut() {
+                *pixel = f(*pixel).map(|c| c.min(max_val));
+            }
+        });
+    }
+
+    /// replace a whole row of pixels.
+    ///
+    /// - y   - y position of the row.
+    /// - row - colors of the pixels, left to right. must be as long as the image is wide, and no channel can be larger than max_val.
+    pub fn set_row(&mut self, y: usize, row: &[[u16; 3]]) -> Result<(), PbmError> {
+        check_row(self.class.width, self.class.height, y, row.len())?;
+        for value in row.iter().flatten() {
+            check_sample(*value, self.class.max_val)?;
+        }
+        self.class.pixels[y].copy_from_slice(row);
+        Ok(())
+    }
+
+    /// get a whole row of pixels.
+    ///
+    /// - y - y position of the row.
+    ///
+    /// returns - colors of the pixels, left to right, or None if the row is not in the image.
+    pub fn get_row(&self, y: usize) -> Option<&[[u16; 3]]> {
+        self.class.pixels.get(y).map(Vec::as_slice)
+    }
+
+    /// write pixels one after another, left to right and top to bottom.
+    ///
+    /// returns - a writer starting at the top left pixel.
+    pub fn writer(&mut self) -> PixelWriter<'_, [u16; 3]> {
+        PixelWriter {
+            pixels: &mut self.class.pixels,
+            width: self.class.width,
+            max_val: self.class.max_val,
+            check: |color, max_val| {
+                color
+                    .iter()
+                    .try_for_each(|value| check_sample(*value, max_val))
+            },
+            position: 0,
+        }
+    }
+
+    /// replace the region of pixels connected to (x, y) that match its color.
+    ///
+    /// pixels are connected through their left, right, top and bottom neighbours.
+    ///
+    /// - x     - x position of the starting pixel. does nothing if not in image.
+    /// - y     - y position of the starting pixel. does nothing if not in image.
+    /// - color - new color of the region. does nothing if any channel is above max_val.
+    pub fn flood_fill(&mut self, x: usize, y: usize, color: [u16; 3]) {
+        if color.iter().all(|c| c <= &self.class.max_val) {
+            flood_fill_pixels(&mut self.class.pixels, x, y, color);
+        }
+    }
+
+    /// get the smallest max_val that can hold every channel without losing data.
+    ///
+    /// returns - the largest channel in the image, or 0 if the image is empty.
+    pub fn min_max_val(&self) -> u16 {
+        self.class
+            .pixels
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// combine this image with another one, sample by sample.
+    ///
+    /// - other - image with the same size and max_val.
+    /// - mode  - how to combine the samples. results are clamped to max_val.
+    ///
+    /// returns - the combined image, or an error if the images don't match.
+    pub fn blend(&self, other: &Self, mode: BlendMode) -> Result<Self, PbmError> {
+        check_same_shape(
+            (self.class.width, self.class.height, self.class.max_val),
+            (other.class.width, other.class.height, other.class.max_val),
+        )?;
+
+        let max_val = self.class.max_val;
+        let mut blended = Self::new_ppm(self.class.width, self.class.height, max_val);
+        for ((out, a), b) in blended
+            .class
+            .pixels
+            .iter_mut()
+            .flatten()
+            .zip(self.class.pixels.iter().flatten())
+            .zip(other.class.pixels.iter().flatten())
+        {
+            *out = core::array::from_fn(|i| blend_sample(a[i], b[i], max_val, mode));
+        }
+        Ok(blended)
+    }
+
+    /// blur the image with a gaussian kernel.
+    ///
+    /// - sigma - standard deviation of the kernel in pixels. must be larger than 0.
+    #[cfg(feature = "std")]
+    pub fn gaussian_blur(&mut self, sigma: f64) -> Result<(), PbmError> {
+        let kernel = gaussian_kernel(sigma)?;
+        for channel in 0..3 {
+            let plane: Vec<f64> = self
+                .class
+                .pixels
+                .iter()
+                .flatten()
+                .map(|x| x[channel] as f64)
+                .collect();
+            let blurred = convolve_plane(&plane, self.class.width, self.class.height, &kernel);
+            for (pixel, value) in self.class.pixels.iter_mut().flatten().zip(blurred) {
+                pixel[channel] = value.round().clamp(0.0, self.class.max_val as f64) as u16;
+            }
+        }
+        Ok(())
+    }
+
+    /// sharpen the image by adding the difference between it and a blurred copy.
+    ///
+    /// - sigma  - standard deviation of the blur in pixels. must be larger than 0.
+    /// - amount - how much of the difference to add. 0 leaves the image unchanged.
+    #[cfg(feature = "std")]
+    pub fn unsharp_mask(&mut self, sigma: f64, amount: f64) -> Result<(), PbmError> {
+        let kernel = gaussian_kernel(sigma)?;
+        for channel in 0..3 {
+            let plane: Vec<f64> = self
+                .class
+                .pixels
+                .iter()
+                .flatten()
+                .map(|x| x[channel] as f64)
+                .collect();
+            let blurred = convolve_plane(&plane, self.class.width, self.class.height, &kernel);
+            for (pixel, value) in self.class.pixels.iter_mut().flatten().zip(blurred) {
+                let original = pixel[channel] as f64;
+                let sharpened = original + amount * (original - value);
+                pixel[channel] = sharpened.round().clamp(0.0, self.class.max_val as f64) as u16;
+            }
+        }
+        Ok(())
+    }
+
+    /// rotate the image counter-clockwise around its center, with bilinear sampling.
+    ///
+    /// the image grows to fit the rotated corners.
+    ///
+    /// - degrees - angle to rotate by.
+    /// - fill    - color of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the rotated image.
+    #[cfg(feature = "std")]
+    pub fn rotate(&self, degrees: f64, fill: [u16; 3]) -> Self {
+        let (width, height) = (self.class.width, self.class.height);
+        let (out_width, out_height, map) = rotated_bounds(width, height, degrees);
+        NetPPMFile {
+            width: out_width,
+            height: out_height,
+            max_val: self.class.max_val,
+            pixels: warp_pixels(
+                |x, y| self.class.pixels[y][x],
+                width,
+                height,
+                out_width,
+                out_height,
+                map,
+                fill.map(|c| c.min(self.class.max_val)),
+            ),
+        }
+        .into()
+    }
+
+    /// shear the image, with nearest neighbour sampling.
+    ///
+    /// a pixel at (x, y) moves to (x + shear_x * y, y + shear_y * x), and the image grows to fit.
+    /// if shear_x * shear_y is 1 the image collapses to a line, and every pixel is fill.
+    ///
+    /// - shear_x - horizontal shift per row.
+    /// - shear_y - vertical shift per column.
+    /// - fill    - color of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the sheared image.
+    #[cfg(feature = "std")]
+    pub fn shear(&self, shear_x: f64, shear_y: f64, fill: [u16; 3]) -> Self {
+        let (width, height, pixels) = shear_pixels(
+            &self.class.pixels,
+            self.class.width,
+            self.class.height,
+            shear_x,
+            shear_y,
+            fill.map(|c| c.min(self.class.max_val)),
+        );
+        NetPPMFile {
+            width,
+            height,
+            max_val: self.class.max_val,
+            pixels,
+        }
+        .into()
+    }
+
+    /// transform the image with an affine matrix, with bilinear sampling.
+    ///
+    /// a position (x, y) in the image moves to (a * x + b * y + c, d * x + e * y + f).
+    ///
+    /// - matrix - the rows [a, b, c] and [d, e, f]. a singular matrix gives an image of fill.
+    /// - out_w  - width of the output image.
+    /// - out_h  - height of the output image.
+    /// - fill   - color of pixels outside the original image. clamped to max_val.
+    ///
+    /// returns - the transformed image.
+    #[cfg(feature = "std")]
+    pub fn warp_affine(
+        &self,
+        matrix: [[f64; 3]; 2],
+        out_w: usize,
+        out_h: usize,
+        fill: [u16; 3],
+    ) -> Self {
+        NetPPMFile {
+            width: out_w,
+            height: out_h,
+            max_val: self.class.max_val,
+            pixels: warp_pixels(
+                |x, y| self.class.pixels[y][x],
+                self.class.width,
+                self.class.height,
+                out_w,
+                out_h,
+                inverse_affine_map(matrix),
+                fill.map(|c| c.min(self.class.max_val)),
+            ),
+        }
+        .into()
+    }
+
+    /// check that the image is internally consistent.
+    ///
+    /// returns - an error if the pixels don't match the size of the image or exceed max_val.
+    pub fn validate(&self) -> Result<(), PbmError> {
+        check_grid(&self.class.pixels, self.class.width, self.class.height)?;
+        for value in self.class.pixels.iter().flatten().flatten() {
+            check_sample(*value, self.class.max_val)?;
+        }
+        Ok(())
+    }
+
+    /// shrink the image by averaging square blocks of pixels.
+    ///
+    /// - factor - width and height of each block. blocks on the edges average only the pixels they cover.
+    ///
+    /// returns - the smaller image, or an error if factor is 0.
+    pub fn downsample(&self, factor: usize) -> Result<Self, PbmError> {
+        if factor == 0 {
+            return Err(PbmError::ZeroFactor);
+        }
+        Ok(NetPPMFile {
+            width: self.class.width.div_ceil(factor),
+            height: self.class.height.div_ceil(factor),
+            max_val: self.class.max_val,
+            pixels: downsample_pixels(
+                |x, y| self.class.pixels[y][x],
+                self.class.width,
+                self.class.height,
+                factor,
+            ),
+        }
+        .into())
+    }
+
+    /// shrink the image by averaging square blocks of pixels in linear light.
+    /// averaging the encoded samples directly makes fine detail, like a checkerboard, too dark.
+    ///
+    /// - factor - width and height of each block. blocks on the edges average only the pixels they cover.
+    /// - gamma  - gamma the samples are encoded with, like 2.2.
+    ///
+    /// returns - the smaller image, or an error if factor is 0 or gamma isn't a positive number.
+    /// like downsample this returns a Result rather than Self, since neither has a sensible image.
+    #[cfg(feature = "std")]
+    pub fn downsample_gamma_correct(&self, factor: usize, gamma: f64) -> Result<Self, PbmError> {
+        if factor == 0 {
+            return Err(PbmError::ZeroFactor);
+        }
+        if !(gamma.is_finite() && gamma > 0.0) {
+            return Err(PbmError::InvalidGamma { gamma });
+        }
+
+        let max_val = self.class.max_val;
+        let scale = f64::from(max_val.max(1));
+        let linear: Vec<f64> = (0..=max_val)
+            .map(|sample| (f64::from(sample) / scale).powf(gamma))
+            .collect();
+        let (width, height) = (self.class.width, self.class.height);
+
+        let pixels = (0..height.div_ceil(factor))
+            .map(|by| {
+                (0..width.div_ceil(factor))
+                    .map(|bx| {
+                        let xs = bx * factor..((bx + 1) * factor).min(width);
+                        let ys = by * factor..((by + 1) * factor).min(height);
+                        let count = (xs.len() * ys.len()) as f64;
+                        let mut sums = [0.0; 3];
+                        for row in &self.class.pixels[ys] {
+                            for color in &row[xs.clone()] {
+                                for (sum, &c) in sums.iter_mut().zip(color) {
+                                    *sum += linear[usize::from(c.min(max_val))];
+                                }
+                            }
+                        }
+                        sums.map(|sum| ((sum / count).powf(1.0 / gamma) * scale).round() as u16)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(NetPPMFile {
+            width: width.div_ceil(factor),
+            height: height.div_ceil(factor),
+            max_val,
+            pixels,
+        }
+        .into())
+    }
+
+    /// repeatedly halve the image by averaging 2x2 blocks, until it is 1x1.
+    ///
+    /// returns - every level, starting with a copy of the original image.
+    pub fn mipmap_pyramid(&self) -> Vec<Self> {
+        let mut levels = vec![self.clone()];
+        while let Some(level) = levels.last()
+            && (level.class.width > 1 || level.class.height > 1)
+        {
+            let next = level.downsample(2).unwrap();
+            levels.push(next);
+        }
+        levels
+    }
+
+    /// make the image narrower by removing the least noticeable paths of pixels.
+    ///
+    /// each removed seam is a connected top to bottom path with the smallest total gradient.
+    ///
+    /// - remove_columns - number of seams to remove. the image is at most emptied.
+    ///
+    /// returns - the narrower image.
+    pub fn carve_seams(&self, remove_columns: usize) -> NetPBM<NetPPMFile> {
+        let mut pixels = self.class.pixels.clone();
+        let mut width = self.class.width;
+        for _ in 0..remove_columns.min(width) {
+            if self.class.height > 0 {
+                let seam = min_vertical_seam(&pixels, width);
+                for (row, x) in pixels.iter_mut().zip(seam) {
+                    row.remove(x);
+                }
+            }
+            width -= 1;
+        }
+        NetPPMFile {
+            width,
+            height: self.class.height,
+            max_val: self.class.max_val,
+            pixels,
+        }
+        .into()
+    }
+
+    /// draw another image over this one, partly see-through.
+    ///
+    /// - logo    - image to draw. parts outside this image are skipped.
+    /// - x       - x position of the top left of logo.
+    /// - y       - y position of the top left of logo.
+    /// - opacity - how much logo covers this image, from 0.0 to 1.0. clamped.
+    ///
+    /// returns - an error if logo has a different max_val.
+    #[cfg(feature = "std")]
+    pub fn watermark(
+        &mut self,
+        logo: &NetPBM<NetPPMFile>,
+        x: usize,
+        y: usize,
+        opacity: f64,
+    ) -> Result<(), PbmError> {
+        if logo.class.max_val != self.class.max_val {
+            return Err(PbmError::MaxValMismatch {
+                expected: self.class.max_val,
+                found: logo.class.max_val,
+            });
+        }
+
+        let opacity = opacity.clamp(0.0, 1.0);
+        for (row, logo_row) in self.class.pixels.iter_mut().skip(y).zip(&logo.class.pixels) {
+            for (pixel, logo_pixel) in row.iter_mut().skip(x).zip(logo_row) {
+                for (channel, logo_channel) in pixel.iter_mut().zip(logo_pixel) {
+                    let blended =
+                        *channel as f64 * (1.0 - opacity) + *logo_channel as f64 * opacity;
+                    *channel = blended.round() as u16;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// hash every row of pixels, to find which rows differ between images.
+    ///
+    /// returns - a stable FNV-1a hash of each row, top to bottom.
+    pub fn row_hashes(&self) -> Vec<u64> {
+        self.class
+            .pixels
+            .iter()
+            .map(|row| fnv1a(row.iter().flatten().flat_map(|x| x.to_be_bytes())))
+            .collect()
+    }
+
+    /// hash the whole image, for caching work done on it.
+    ///
+    /// returns - a stable FNV-1a hash of the size, max_val and every pixel.
+    pub fn content_hash(&self) -> u64 {
+        let header = format!(
+            "P3 {} {} {}\n",
+            self.class.width, self.class.height, self.class.max_val
+        );
+        fnv1a(
+            header.bytes().chain(
+                self.class
+                    .pixels
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .flat_map(|x| x.to_be_bytes()),
+            ),
+        )
+    }
+
+    /// crop the largest centered rectangle with the given aspect ratio.
+    ///
+    /// - aspect_w - width part of the ratio. if either part is 0, the image is copied as is.
+    /// - aspect_h - height part of the ratio.
+    ///
+    /// returns - the cropped image.
+    pub fn crop_to_aspect(&self, aspect_w: usize, aspect_h: usize) -> Self {
+        let (width, height) = (self.class.width, self.class.height);
+        if aspect_w == 0 || aspect_h == 0 {
+            return self.clone();
+        }
+
+        let (crop_w, crop_h) =
+            if width as u128 * aspect_h as u128 >= height as u128 * aspect_w as u128 {
+                (
+                    (height as u128 * aspect_w as u128 / aspect_h as u128) as usize,
+                    height,
+                )
+            } else {
+                (
+                    width,
+                    (width as u128 * aspect_h as u128 / aspect_w as u128) as usize,
+                )
+            };
+        let x = (width - crop_w) / 2;
+        let y = (height - crop_h) / 2;
+
+        NetPPMFile {
+            width: crop_w,
+            height: crop_h,
+            max_val: self.class.max_val,
+            pixels: self.class.pixels[y..y + crop_h]
+                .iter()
+                .map(|row| row[x..x + crop_w].to_vec())
+                .collect(),
+        }
+        .into()
+    }
+
+    /// replace every channel with its entry in a lookup table for that channel.
+    ///
+    /// - luts - red, green and blue tables. each must have max_val + 1 entries, none larger than max_val.
+    pub fn apply_lut(&mut self, luts: [&[u16]; 3]) -> Result<(), PbmError> {
+        for lut in luts {
+            check_lut(lut, self.class.max_val)?;
+        }
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            for (channel, lut) in pixel.iter_mut().zip(luts) {
+                *channel = lut[*channel as usize];
+            }
+        }
+        Ok(())
+    }
+
+    /// convert the image to grayscale using Rec. 601 luma.
+    ///
+    /// returns - a PGM with the same size and max_val.
+    pub fn to_pgm(&self) -> NetPBM<NetPGMFile> {
+        self.weighted_pgm(LumaCoeffs::Rec601.weights())
+    }
+
+    /// convert the image to grayscale with the given luma coefficients.
+    ///
+    /// - coeffs - weights of the red, green and blue channels. results are clamped to max_val.
+    ///
+    /// returns - a PGM with the same size and max_val.
+    pub fn to_pgm_with(&self, coeffs: LumaCoeffs) -> Result<NetPBM<NetPGMFile>, PbmError> {
+        let weights = coeffs.weights();
+        if weights.iter().any(|w| w.is_nan() || *w < 0.0) {
+            return Err(PbmError::InvalidCoefficients { coeffs: weights });
+        }
+        Ok(self.weighted_pgm(weights))
+    }
+
+    /// convert the image to grayscale with weights that are already known to be valid.
+    fn weighted_pgm(&self, weights: [f64; 3]) -> NetPBM<NetPGMFile> {
+        let max_val = self.class.max_val;
+        let pixels = self
+            .class
+            .pixels
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|color| {
+                        let luma: f64 = color
+                            .iter()
+                            .zip(weights)
+                            .map(|(&c, w)| f64::from(c) * w)
+                            .sum();
+                        ((luma + 0.5) as u16).min(max_val)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        NetPGMFile {
+            width: self.class.width,
+            height: self.class.height,
+            max_val,
+            pixels,
+        }
+        .into()
+    }
+
+    /// shift the image, wrapping pixels that leave one edge around to the opposite edge.
+    ///
+    /// - dx - pixels to shift right. negative shifts left.
+    /// - dy - pixels to shift down. negative shifts up.
+    pub fn roll(&mut self, dx: isize, dy: isize) {
+        roll_pixels(&mut self.class.pixels, dx, dy);
+    }
+
+    /// check that saving the image in binary and loading it again gives back the same pixels.
+    ///
+    /// returns - true if the loaded image is equal to this one.
+    pub fn roundtrips_raw(&self) -> bool {
+        parse_ppm(&self.to_raw(), 0, ParseMode::Strict)
+            .is_ok_and(|(image, _)| image.class == self.class)
+    }
+
+    /// fill the image with a linear gradient between two colors.
+    ///
+    /// - from     - color of the left column, or top row if vertical. clamped to max_val.
+    /// - to       - color of the right column, or bottom row if vertical. clamped to max_val.
+    /// - vertical - whether the gradient goes top to bottom instead of left to right.
+    pub fn fill_gradient(&mut self, from: [u16; 3], to: [u16; 3], vertical: bool) {
+        let max_val = self.class.max_val;
+        let (from, to) = (from.map(|c| c.min(max_val)), to.map(|c| c.min(max_val)));
+        let len = if vertical {
+            self.class.height
+        } else {
+            self.class.width
+        };
+        for (y, row) in self.class.pixels.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let i = if vertical { y } else { x };
+                *pixel = core::array::from_fn(|c| lerp_sample(from[c], to[c], i, len));
+            }
+        }
+    }
+
+    /// remove the rows and columns around the edge that are all close to one color.
+    ///
+    /// - color     - color of the border.
+    /// - tolerance - how far a sample can be from the border and still count as border.
+    ///
+    /// returns - the trimmed image, or a 0x0 image if every pixel is border.
+    pub fn trim_border(&self, color: [u16; 3], tolerance: u16) -> Self {
+        let (x, y, width, height) = content_bounds(&self.class.pixels, |pixel| {
+            pixel
+                .iter()
+                .zip(color)
+                .all(|(&c, b)| c.abs_diff(b) <= tolerance)
+        })
+        .unwrap_or_default();
+        NetPPMFile {
+            width,
+            height,
+            max_val: self.class.max_val,
+            pixels: crop_pixels(&self.class.pixels, x, y, width, height),
+        }
+        .into()
+    }
+
+    /// draw evenly spaced horizontal and vertical lines, starting at the top left corner.
+    ///
+    /// - spacing - distance between lines. does nothing if 0.
+    /// - color   - color of the lines. rgb order. does nothing if above max_val.
+    pub fn draw_grid(&mut self, spacing: usize, color: [u16; 3]) {
+        if color.iter().all(|&c| c <= self.class.max_val) {
+            grid_pixels(&mut self.class.pixels, spacing, color);
+        }
+    }
+
+    /// replace every pixel close to one color with another.
+    /// closeness is the euclidean distance between the colors in rgb space.
+    ///
+    /// - from      - color to replace. rgb order.
+    /// - to        - color to replace it with. rgb order. clamped to max_val.
+    /// - tolerance - how far a pixel can be from from and still be replaced.
+    pub fn replace_color(&mut self, from: [u16; 3], to: [u16; 3], tolerance: u16) {
+        let to = to.map(|c| c.min(self.class.max_val));
+        let tolerance = u64::from(tolerance).pow(2);
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            if color_distance_squared(*pixel, from) <= tolerance {
+                *pixel = to;
+            }
+        }
+    }
+
+    /// show where two images differ, for comparing them by eye.
+    ///
+    /// - other - image with the same size and max_val.
+    ///
+    /// returns - a PGM where each pixel is the summed difference of the channels, scaled to max_val.
+    pub fn difference_image(&self, other: &Self) -> Result<NetPBM<NetPGMFile>, PbmError> {
+        check_same_shape(
+            (self.class.width, self.class.height, self.class.max_val),
+            (other.class.width, other.class.height, other.class.max_val),
+        )?;
+        let pixels = self
+            .class
+            .pixels
+            .iter()
+            .zip(&other.class.pixels)
+            .map(|(row, other_row)| {
+                row.iter()
+                    .zip(other_row)
+                    .map(|(a, b)| {
+                        let sum: u32 = a
+                            .iter()
+                            .zip(b)
+                            .map(|(&a, &b)| u32::from(a.abs_diff(b)))
+                            .sum();
+                        ((sum + 1) / 3) as u16
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(NetPGMFile {
+            width: self.class.width,
+            height: self.class.height,
+            max_val: self.class.max_val,
+            pixels,
+        }
+        .into())
+    }
+
+    /// read the image at a fractional position, interpolating between the four nearest pixels.
+    ///
+    /// - x - x position, from 0.0 to width - 1.
+    /// - y - y position, from 0.0 to height - 1.
+    ///
+    /// returns - the interpolated color, or None outside the image instead of clamping.
+    #[cfg(feature = "std")]
+    pub fn sample_bilinear(&self, x: f64, y: f64) -> Option<[u16; 3]> {
+        let (width, height) = (self.class.width, self.class.height);
+        let inside = |v: f64, len: usize| len > 0 && (0.0..=(len - 1) as f64).contains(&v);
+        if !(inside(x, width) && inside(y, height)) {
+            return None;
+        }
+        let sampled = sample_bilinear_clamped(|x, y| self.class.pixels[y][x], x, y, width, height);
+        Some(sampled)
+    }
+
+    /// brighten every pixel by a constant per channel, stopping at max_val.
+    ///
+    /// - delta - amount to add to the red, green and blue channels.
+    pub fn saturating_add(&mut self, delta: [u16; 3]) {
+        let max_val = self.class.max_val;
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            for (channel, d) in pixel.iter_mut().zip(delta) {
+                *channel = channel.saturating_add(d).min(max_val);
+            }
+        }
+    }
+
+    /// darken every pixel by a constant per channel, stopping at 0.
+    ///
+    /// - delta - amount to subtract from the red, green and blue channels.
+    pub fn saturating_sub(&mut self, delta: [u16; 3]) {
+        for pixel in self.class.pixels.iter_mut().flatten() {
+            for (channel, d) in pixel.iter_mut().zip(delta) {
+                *channel = channel.saturating_sub(d);
+            }
+        }
+    }
+
+    /// iterate over the pixels in a rectangle, row by row. an empty rectangle yields nothing.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - width  - width of the rectangle.
+    /// - height - height of the rectangle.
+    ///
+    /// returns - (x, y, color) for each pixel, or an error if the rectangle doesn't fit.
+    pub fn region_pixels(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<impl Iterator<Item = (usize, usize, [u16; 3])>, PbmError> {
+        check_region(self.class.width, self.class.height, x, y, width, height)?;
+        Ok(
+            region_iter(&self.class.pixels, x, y, width, height)
+                .map(|(x, y, pixel)| (x, y, *pixel)),
+        )
+    }
+
+    /// lower every sample above max_val to max_val, so the image is safe to save.
+    pub fn clamp_samples(&mut self) {
+        let max_val = self.class.max_val;
+        for sample in self.class.pixels.iter_mut().flatten().flatten() {
+            *sample = (*sample).min(max_val);
+        }
+    }
+
+    /// mark the pixels that are far from a reference color, like a background.
+    /// distance is the euclidean distance between the colors in rgb space.
+    ///
+    /// - reference - color to measure from. rgb order.
+    /// - threshold - largest distance that still counts as the reference color.
+    ///
+    /// returns - a PBM image, where true is a pixel further than threshold from reference.
+    pub fn color_distance_mask(&self, reference: [u16; 3], threshold: u16) -> NetPBM<NetPBMFile> {
+        let threshold = u64::from(threshold).pow(2);
+        NetPBMFile {
+            width: self.class.width,
+            height: self.class.height,
+            pixels: self
+                .class
+                .pixels
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|&color| color_distance_squared(color, reference) > threshold)
+                        .collect()
+                })
+                .collect(),
+        }
+        .into()
+    }
+
+    /// replace every pixel outside a mask with a background color.
+    ///
+    /// - mask       - PBM image with the same size, where true keeps a pixel.
+    /// - background - color of the removed pixels. rgb order. must not be larger than max_val.
+    pub fn apply_mask(
+        &mut self,
+        mask: &NetPBM<NetPBMFile>,
+        background: [u16; 3],
+    ) -> Result<(), PbmError> {
+        let (width, height) = (self.class.width, self.class.height);
+        if (mask.class.width, mask.class.height) != (width, height) {
+            return Err(PbmError::DimensionMismatch {
+                expected: (width, height),
+                found: (mask.class.width, mask.class.height),
+            });
+        }
+        for channel in background {
+            check_sample(channel, self.class.max_val)?;
+        }
+
+        for (row, mask_row) in self.class.pixels.iter_mut().zip(&mask.class.pixels) {
+            for (pixel, &keep) in row.iter_mut().zip(mask_row) {
+                if !keep {
+                    *pixel = background;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// image types for NetPAM files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TupleType {
+    /// like NetPBM.
+    BlackAndWhite,
+    /// like NetPGM.
+    Grayscale,
+    /// like NetPPM.
+    RGB,
+    /// like NetPBM, but with transparency.
+    BlackAndWhiteAlpha,
+    /// like NetPGM, but with transparency.
+    GrayscaleAlpha,
+    /// like NetPPM, but with transparency.
+    RGBAlpha,
+    /// allows for custom extensible formats to NetPAM.
+    Custom {
+        /// the quantity of bytes per pixel.
+        depth: u16,
+        /// the name used in the header. SHOULD BE UNIQUE!
+        tuple_type: &'static str,
+    },
+}
+
+impl TupleType {
+    fn get_depth(&self) -> u16 {
+        match self {
+            TupleType::BlackAndWhite => 1,
+            TupleType::Grayscale => 1,
+            TupleType::RGB => 3,
+            TupleType::BlackAndWhiteAlpha => 2,
+            TupleType::GrayscaleAlpha => 2,
+            TupleType::RGBAlpha => 4,
+            TupleType::Custom { depth, .. } => *depth,
+        }
+    }
+
+    fn get_tuple_type(&self) -> &str {
+        match self {
+            TupleType::BlackAndWhite => "BLACKANDWHITE",
+            TupleType::Grayscale => "GRAYSCALE",
+            TupleType::RGB => "RGB",
+            TupleType::BlackAndWhiteAlpha => "BLACKANDWHITE_ALPHA",
+            TupleType::GrayscaleAlpha => "GRAYSCALE_ALPHA",
+            TupleType::RGBAlpha => "RGB_ALPHA",
+            TupleType::Custom { tuple_type, .. } => tuple_type,
+        }
+    }
+
+    /// find the tuple type a header names.
+    ///
+    /// custom names can't be kept, since they have to be static, so they load as an unnamed
+    /// Custom type. read_header gives the name.
+    ///
+    /// - tuple_type - the TUPLTYPE value.
+    /// - depth      - the DEPTH value, used for custom types.
+    ///
+    /// returns - the tuple type, or None if depth doesn't fit in a u16.
+    fn from_tuple_type(tuple_type: &str, depth: usize) -> Option<Self> {
+        let depth = u16::try_from(depth).ok()?;
+        Some(match tuple_type {
+            "BLACKANDWHITE" => TupleType::BlackAndWhite,
+            "GRAYSCALE" => TupleType::Grayscale,
+            "RGB" => TupleType::RGB,
+            "BLACKANDWHITE_ALPHA" => TupleType::BlackAndWhiteAlpha,
+            "GRAYSCALE_ALPHA" => TupleType::GrayscaleAlpha,
+            "RGB_ALPHA" => TupleType::RGBAlpha,
+            _ => TupleType::Custom {
+                depth,
+                tuple_type: "",
+            },
+        })
+    }
+}
+
+/// type for NetPAM files.
+#[derive(Debug, PartialEq)]
+pub struct NetPAM {
+    width: usize,
+    height: usize,
+    depth: usize,
+    max_val: u16,
+    tuple_type: TupleType,
+    pixels: Vec<Vec<Vec<u16>>>,
+    gamma: Option<f64>,
+}
+
+impl NetPAM {
+    /// create a new NetPAM image.
+    pub fn new(width: usize, height: usize, max_val: u16, tuple_type: TupleType) -> Self {
+        let depth = tuple_type.get_depth() as usize;
+        let pixels = vec![vec![vec![0; depth]; width]; height];
+
+        Self {
+            width,
+            height,
+            depth,
+            max_val,
+            tuple_type,
+            pixels,
+            gamma: None,
+        }
+    }
+
+    /// get the image type.
+    ///
+    /// returns - the tuple type of the image.
+    pub fn tuple_type(&self) -> &TupleType {
+        &self.tuple_type
+    }
+
+    /// get the number of samples per pixel.
+    ///
+    /// returns - the depth of the image.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// get the gamma the samples are assumed to be encoded with.
+    ///
+    /// returns - the gamma from the GAMMA comment, or None if the image has none.
+    pub fn gamma(&self) -> Option<f64> {
+        self.gamma
+    }
+
+    /// set the gamma the samples are assumed to be encoded with.
+    /// it is saved as a "# GAMMA" comment, which other readers ignore.
+    ///
+    /// - gamma - the gamma, or None to not write the comment.
+    ///
+    /// returns - an error if gamma is not a finite number larger than 0.
+    pub fn set_gamma(&mut self, gamma: Option<f64>) -> Result<(), PbmError> {
+        if let Some(gamma) = gamma
+            && !(gamma.is_finite() && gamma > 0.0)
+        {
+            return Err(PbmError::InvalidGamma { gamma });
+        }
+        self.gamma = gamma;
+        Ok(())
+    }
+
+    /// set a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - color - color of pixel. (rgb|v)a order. 0 is black, max_val is white.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Vec<u16>) {
+        if x < self.width
+            && y < self.height
+            && color.len() == self.depth
+            && color.iter().all(|x| x <= &self.max_val)
+        {
+            self.pixels[y][x] = color;
+        }
+    }
+
+    /// set a pixels color at signed coordinates.
+    ///
+    /// - x     - x position of pixel. does nothing if negative or not in image.
+    /// - y     - y position of pixel. does nothing if negative or not in image.
+    /// - color - color of pixel. (rgb|v)a order. 0 is black, max_val is white.
+    pub fn set_pixel_signed(&mut self, x: i32, y: i32, color: Vec<u16>) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            self.set_pixel(x, y, color);
+        }
+    }
+
+    /// get a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - color of pixel. (rgb|v)a order. 0 is black, max_val is white.
+    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<Vec<u16>> {
+        if x < self.width && y < self.height {
+            return Some(self.pixels[y][x].clone());
+        }
+        None
+    }
+
+    /// swap the x and y axes of the image.
+    ///
+    /// returns - a new image where the pixel at (x, y) is the original pixel at (y, x).
+    pub fn transpose(&self) -> Self {
+        Self {
+            width: self.height,
+            height: self.width,
+            depth: self.depth,
+            max_val: self.max_val,
+            tuple_type: self.tuple_type.clone(),
+            pixels: transpose_pixels(&self.pixels, self.width),
+            gamma: self.gamma,
+        }
+    }
+
+    /// convert the image to its binary representation.
+    ///
+    /// returns - binary representation of the image.
+    pub fn to_raw(&self) -> Vec<u8> {
+        self.to_raw_with(ByteOrder::BigEndian)
+    }
+
+    /// convert the image to its binary representation, with a choice of 16-bit sample order.
+    ///
+    /// - byte_order - order of the bytes in 16-bit samples. little endian is not standard.
+    ///
+    /// returns - binary representation of the image.
+    pub fn to_raw_with(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.raw_size());
+        self.write_raw_into_with(&mut raw, byte_order);
+        raw
+    }
+
+    /// append the binary representation of the image to a buffer.
+    ///
+    /// - buf - buffer to append to. its existing contents are kept.
+    pub fn write_raw_into(&self, buf: &mut Vec<u8>) {
+        self.write_raw_into_with(buf, ByteOrder::BigEndian);
+    }
+
+    fn write_raw_into_with(&self, buf: &mut Vec<u8>, byte_order: ByteOrder) {
+        buf.extend_from_slice(self.header().as_bytes());
+        for x in self.pixels.iter().flatten().flatten() {
+            write_sample(buf, *x, self.max_val, byte_order);
+        }
+    }
+
+    /// get the length of the binary representation without creating it.
+    ///
+    /// returns - the exact number of bytes to_raw produces.
+    pub fn raw_size(&self) -> usize {
+        self.header().len() + self.width * self.height * self.depth * bytes_per_sample(self.max_val)
+    }
+
+    /// the header of the binary representation, up to and including ENDHDR.
+    fn header(&self) -> String {
+        let mut header = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\n",
+            self.width,
+            self.height,
+            self.depth,
+            self.max_val,
+            self.tuple_type.get_tuple_type(),
+        );
+        if let Some(gamma) = self.gamma {
+            header += &format!("# GAMMA {}\n", gamma);
+        }
+        header + "ENDHDR\n"
+    }
+
+    /// save the image in its binary representation.
+    #[cfg(feature = "std")]
+    pub fn save_raw(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_raw())?;
+        Ok(())
+    }
+
+    /// save the image, which must be binary for NetPAM files.
+    ///
+    /// - path     - where to save the image.
+    /// - encoding - must be binary. ASCII returns an unsupported error.
+    /// - comment  - ignored, since NetPAM files are only binary.
+    #[cfg(feature = "std")]
+    pub fn save(
+        &self,
+        path: &str,
+        encoding: Encoding,
+        _comment: Option<&str>,
+    ) -> std::io::Result<()> {
+        match encoding {
+            Encoding::Ascii => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "NetPAM files have no ASCII representation",
+            )),
+            Encoding::Binary => self.save_raw(path),
+        }
+    }
+
+    /// replace a whole row of pixels.
+    ///
+    /// - y   - y position of the row.
+    /// - row - colors of the pixels, left to right. must be as long as the image is wide.
+    ///
+    /// every color must have depth samples, and no sample can be larger than max_val.
+    pub fn set_row(&mut self, y: usize, row: &[Vec<u16>]) -> Result<(), PbmError> {
+        check_row(self.width, self.height, y, row.len())?;
+        for color in row {
+            if color.len() != self.depth {
+                return Err(PbmError::DepthMismatch {
+                    expected: self.depth,
+                    found: color.len(),
+                });
+            }
+            for value in color {
+                check_sample(*value, self.max_val)?;
+            }
+        }
+        self.pixels[y] = row.to_vec();
+        Ok(())
+    }
+
+    /// get a whole row of pixels.
+    ///
+    /// - y - y position of the row.
+    ///
+    /// returns - colors of the pixels, left to right, or None if the row is not in the image.
+    pub fn get_row(&self, y: usize) -> Option<&[Vec<u16>]> {
+        self.pixels.get(y).map(Vec::as_slice)
+    }
+
+    /// replace the region of pixels connected to (x, y) that match its color.
+    ///
+    /// pixels are connected through their left, right, top and bottom neighbours.
+    ///
+    /// - x     - x position of the starting pixel. does nothing if not in image.
+    /// - y     - y position of the starting pixel. does nothing if not in image.
+    /// - color - new color of the region. does nothing if it is not a valid color.
+    pub fn flood_fill(&mut self, x: usize, y: usize, color: Vec<u16>) {
+        if color.len() == self.depth && color.iter().all(|c| c <= &self.max_val) {
+            flood_fill_pixels(&mut self.pixels, x, y, color);
+        }
+    }
+
+    /// split a GrayscaleAlpha image into its gray and alpha planes.
+    ///
+    /// returns - the gray plane and the alpha plane, both with the max_val of the image.
+    pub fn split_alpha(&self) -> Result<(NetPBM<NetPGMFile>, NetPBM<NetPGMFile>), PbmError> {
+        if self.tuple_type != TupleType::GrayscaleAlpha {
+            return Err(PbmError::TupleTypeMismatch {
+                expected: TupleType::GrayscaleAlpha,
+                found: self.tuple_type.clone(),
+            });
+        }
+        Ok((self.plane(|pixel| pixel[0]), self.plane(|pixel| pixel[1])))
+    }
+
+    /// split a RGBAlpha image into its color and alpha planes.
+    ///
+    /// returns - the color plane and the alpha plane, both with the max_val of the image.
+    pub fn split_alpha_rgb(&self) -> Result<(NetPBM<NetPPMFile>, NetPBM<NetPGMFile>), PbmError> {
+        if self.tuple_type != TupleType::RGBAlpha {
+            return Err(PbmError::TupleTypeMismatch {
+                expected: TupleType::RGBAlpha,
+                found: self.tuple_type.clone(),
+            });
+        }
+        let color = NetPPMFile {
+            width: self.width,
+            height: self.height,
+            max_val: self.max_val,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                        .collect()
+                })
+                .collect(),
+        };
+        Ok((color.into(), self.plane(|pixel| pixel[3])))
+    }
+
+    fn plane(&self, sample: impl Fn(&[u16]) -> u16) -> NetPBM<NetPGMFile> {
+        NetPGMFile {
+            width: self.width,
+            height: self.height,
+            max_val: self.max_val,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|row| row.iter().map(|pixel| sample(pixel)).collect())
+                .collect(),
+        }
+        .into()
+    }
+
+    /// check that the image is internally consistent.
+    ///
+    /// returns - an error if the pixels don't match the size or depth of the image or exceed max_val.
+    pub fn validate(&self) -> Result<(), PbmError> {
+        check_grid(&self.pixels, self.width, self.height)?;
+        for color in self.pixels.iter().flatten() {
+            if color.len() != self.depth {
+                return Err(PbmError::DepthMismatch {
+                    expected: self.depth,
+                    found: color.len(),
+                });
+            }
+            for value in color {
+                check_sample(*value, self.max_val)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// hash every row of pixels, to find which rows differ between images.
+    ///
+    /// returns - a stable FNV-1a hash of each row, top to bottom.
+    pub fn row_hashes(&self) -> Vec<u64> {
+        self.pixels
+            .iter()
+            .map(|row| fnv1a(row.iter().flatten().flat_map(|x| x.to_be_bytes())))
+            .collect()
+    }
+
+    /// hash the whole image, for caching work done on it.
+    ///
+    /// returns - a stable FNV-1a hash of the size, depth, max_val, tuple type and every pixel.
+    pub fn content_hash(&self) -> u64 {
+        let header = format!(
+            "P7 {} {} {} {} {}\n",
+            self.width,
+            self.height,
+            self.depth,
+            self.max_val,
+            self.tuple_type.get_tuple_type()
+        );
+        fnv1a(
+            header.bytes().chain(
+                self.pixels
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .flat_map(|x| x.to_be_bytes()),
+            ),
+        )
+    }
+
+    /// shift the image, wrapping pixels that leave one edge around to the opposite edge.
+    ///
+    /// - dx - pixels to shift right. negative shifts left.
+    /// - dy - pixels to shift down. negative shifts up.
+    pub fn roll(&mut self, dx: isize, dy: isize) {
+        roll_pixels(&mut self.pixels, dx, dy);
+    }
+
+    /// check that saving the image in binary and loading it again gives back the same pixels.
+    ///
+    /// returns - true if the loaded image is equal to this one.
+    pub fn roundtrips_raw(&self) -> bool {
+        parse_pam(&self.to_raw(), 0).is_ok_and(|(image, _)| image == *self)
+    }
+
+    /// draw evenly spaced horizontal and vertical lines, starting at the top left corner.
+    ///
+    /// - spacing - distance between lines. does nothing if 0.
+    /// - color   - color of the lines. (rgb|v)a order. does nothing if invalid for the image.
+    pub fn draw_grid(&mut self, spacing: usize, color: Vec<u16>) {
+        if color.len() == self.depth && color.iter().all(|&c| c <= self.max_val) {
+            grid_pixels(&mut self.pixels, spacing, color);
+        }
+    }
+
+    /// iterate over the pixels in a rectangle, row by row. an empty rectangle yields nothing.
+    ///
+    /// - x      - x position of the rectangle.
+    /// - y      - y position of the rectangle.
+    /// - width  - width of the rectangle.
+    /// - height - height of the rectangle.
+    ///
+    /// returns - (x, y, color) for each pixel, or an error if the rectangle doesn't fit.
+    pub fn region_pixels(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<impl Iterator<Item = (usize, usize, &[u16])>, PbmError> {
+        check_region(self.width, self.height, x, y, width, height)?;
+        Ok(region_iter(&self.pixels, x, y, width, height)
+            .map(|(x, y, pixel)| (x, y, pixel.as_slice())))
+    }
+
+    /// lower every sample above max_val to max_val, so the image is safe to save.
+    pub fn clamp_samples(&mut self) {
+        let max_val = self.max_val;
+        for sample in self.pixels.iter_mut().flatten().flatten() {
+            *sample = (*sample).min(max_val);
+        }
+    }
+}
+
+impl core::fmt::Display for NetPAM {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "PAM {}x{} depth={} maxval={} tupltype={}",
+            self.width,
+            self.height,
+            self.depth,
+            self.max_val,
+            self.tuple_type.get_tuple_type()
+        )
+    }
+}
+
+/// type for PFM (portable float map) files.
+#[derive(Debug, PartialEq)]
+pub struct NetPFMFile {
+    width: usize,
+    height: usize,
+    depth: usize,
+    scale: f32,
+    byte_order: ByteOrder,
+    pixels: Vec<Vec<Vec<f32>>>,
+}
+
+impl NetPFMFile {
+    /// create a new PFM File.
+    ///
+    /// - width  - immutable size for image width.
+    /// - height - immutable size for image height.
+    /// - color  - whether pixels are rgb (PF) or grayscale (Pf).
+    pub fn new(width: usize, height: usize, color: bool) -> Self {
+        let depth = if color { 3 } else { 1 };
+        Self {
+            width,
+            height,
+            depth,
+            scale: 1.0,
+            byte_order: ByteOrder::LittleEndian,
+            pixels: vec![vec![vec![0.0; depth]; width]; height],
+        }
+    }
+
+    /// set a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    /// - color - color of pixel. rgb order, or a single value for grayscale.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Vec<f32>) {
+        if x < self.width && y < self.height && color.len() == self.depth {
+            self.pixels[y][x] = color;
+        }
+    }
+
+    /// get a pixels color.
+    ///
+    /// - x     - x position of pixel. does nothing if not in image.
+    /// - y     - y position of pixel. does nothing if not in image.
+    ///
+    /// returns - color of pixel. rgb order, or a single value for grayscale.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Vec<f32>> {
+        if x < self.width && y < self.height {
+            return Some(self.pixels[y][x].clone());
+        }
+        None
+    }
+
+    /// get the scale factor of the image.
+    ///
+    /// returns - the scale factor. always positive.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// set the scale factor of the image.
+    ///
+    /// - scale - the scale factor. the sign is ignored, since it stores the byte order.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.abs();
+    }
+
+    /// get the byte order samples are written in.
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// set the byte order samples are written in.
+    pub fn set_byte_order(&mut self, byte_order: ByteOrder) {
+        self.byte_order = byte_order;
+    }
+
+    /// convert the image to its binary representation.
+    ///
+    /// rows are written bottom to top, as the format requires.
+    ///
+    /// returns - binary representation of the image.
+    pub fn to_raw(&self) -> Vec<u8> {
+        let mut raw = Vec::new();
+        self.write_raw_into(&mut raw);
+        raw
+    }
+
+    /// append the binary representation of the image to a buffer.
+    ///
+    /// - buf - buffer to append to. its existing contents are kept.
+    pub fn write_raw_into(&self, buf: &mut Vec<u8>) {
+        let scale = match self.byte_order {
+            ByteOrder::BigEndian => self.scale,
+            ByteOrder::LittleEndian => -self.scale,
+        };
+        buf.extend_from_slice(
+            format!(
+                "{}\n{} {}\n{}\n",
+                if self.depth == 3 { "PF" } else { "Pf" },
+                self.width,
+                self.height,
+                scale
+            )
+            .as_bytes(),
+        );
+        for x in self.pixels.iter().rev().flatten().flatten() {
+            buf.extend_from_slice(&match self.byte_order {
+                ByteOrder::BigEndian => x.to_be_bytes(),
+                ByteOrder::LittleEndian => x.to_le_bytes(),
+            });
+        }
+    }
+
+    /// save the image in its binary representation.
+    #[cfg(feature = "std")]
+    pub fn save_pfm(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_raw())?;
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for NetPFMFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "PFM {}x{} depth={} scale={}",
+            self.width, self.height, self.depth, self.scale
+        )
+    }
+}
+
+/// create an HSV color wheel.
+///
+/// hue goes around the center, and value fades from white in the center to black at the edges.
+///
+/// - size    - width and height of the image.
+/// - max_val - maximum value of a channel.
+///
+/// returns - an RGB NetPAM image of the color wheel.
+#[cfg(feature = "std")]
+pub fn color_wheel(size: usize, max_val: u16) -> NetPAM {
+    let mut pam = NetPAM::new(size, size, max_val, TupleType::RGB);
+    for x in 0..size {
+        for y in 0..size {
+            let dx = x as f64 - (size / 2) as f64;
+            let dy = y as f64 - (size / 2) as f64;
+
+            let r = (dx * dx + dy * dy).sqrt();
+            let theta = dy.atan2(dx);
+
+            let hue = (theta + core::f64::consts::PI).to_degrees();
+            let saturation = 1.0;
+            let value = 1.0 - (r / size as f64).min(1.0);
+
+            let rgb = hsv_to_rgb(hue, saturation, value);
+            let color = rgb.iter().map(|c| (c * max_val as f64) as u16).collect();
+
+            pam.set_pixel(x, y, color);
+        }
+    }
+    pam
+}
+
+/// convert an HSV color to RGB.
+///
+/// - hue        - hue in degrees, from 0 to 360.
+/// - saturation - saturation from 0 to 1.
+/// - value      - value from 0 to 1.
+///
+/// returns - rgb color with each channel from 0 to 1.
+#[cfg(feature = "std")]
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [f64; 3] {
+    let chroma = saturation * value;
+
+    let hue_prime = hue / 60.0;
+    let intermediate = chroma * (1.0 - (hue_prime.rem_euclid(2.0) - 1.0).abs());
+
+    let color_1 = if (0.0..1.0).contains(&hue_prime) {
+        [chroma, intermediate, 0.0]
+    } else if (1.0..2.0).contains(&hue_prime) {
+        [intermediate, chroma, 0.0]
+    } else if (2.0..3.0).contains(&hue_prime) {
+        [0.0, chroma, intermediate]
+    } else if (3.0..4.0).contains(&hue_prime) {
+        [0.0, intermediate, chroma]
+    } else if (4.0..5.0).contains(&hue_prime) {
+        [intermediate, 0.0, chroma]
+    } else {
+        [chroma, 0.0, intermediate]
+    };
+
+    let m = value - chroma;
+    color_1.map(|c| c + m)
+}
+
+/// combine a color image and an alpha mask into one image.
+///
+/// - color - the rgb samples of the image.
+/// - alpha - the alpha samples of the image. must have the same size and max_val as color.
+///
+/// returns - an RGBAlpha NetPAM image.
+pub fn with_alpha(
+    color: &NetPBM<NetPPMFile>,
+    alpha: &NetPBM<NetPGMFile>,
+) -> Result<NetPAM, PbmError> {
+    let (color, alpha) = (color.inner(), alpha.inner());
+    check_same_shape(
+        (color.width, color.height, color.max_val),
+        (alpha.width, alpha.height, alpha.max_val),
+    )?;
+    Ok(NetPAM {
+        width: color.width,
+        height: color.height,
+        depth: 4,
+        max_val: color.max_val,
+        tuple_type: TupleType::RGBAlpha,
+        pixels: color
+            .pixels
+            .iter()
+            .zip(&alpha.pixels)
+            .map(|(color_row, alpha_row)| {
+                color_row
+                    .iter()
+                    .zip(alpha_row)
+                    .map(|(&[r, g, b], &a)| vec![r, g, b, a])
+                    .collect()
+            })
+            .collect(),
+        gamma: None,
+    })
+}
+
+/// read only the header of a file, without loading its pixels.
+/// any of P1 to P7
+///
+/// - path - where the file is.
+///
+/// returns - the format, size and other metadata of the image.
+#[cfg(feature = "std")]
+pub fn read_header(path: &str) -> Result<Header, PbmError> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path).map_err(|e| PbmError::Io { kind: e.kind() })?;
+    let bytes = std::io::BufReader::new(file).bytes().map_while(Result::ok);
+    parse_header(&mut HeaderReader::new(bytes))
+}
+
+/// load a pbm file from a path.  
+/// either P1 or P4
+///
+/// panics if the file can't be read or parsed. use try_load_pbm to handle that instead.
+#[cfg(feature = "std")]
+pub fn load_pbm(path: &str) -> NetPBM<NetPBMFile> {
+    try_load_pbm(path).unwrap()
+}
+
+/// load a pbm file from a path, returning an error instead of panicking.
+/// either P1 or P4
+///
+/// - path - where the file is.
+///
+/// returns - the image, or an error with the byte offset where parsing failed.
+#[cfg(feature = "std")]
+pub fn try_load_pbm(path: &str) -> Result<NetPBM<NetPBMFile>, PbmError> {
+    parse_pbm(&std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?)
+}
+
+/// load a pbm file from a string.
+/// either P1 or P4
+pub fn load_pbm_str(s: &str) -> Result<NetPBM<NetPBMFile>, PbmError> {
+    parse_pbm(s.as_bytes())
+}
+
+/// parse the bytes of a pbm file.
+fn parse_pbm(file: &[u8]) -> Result<NetPBM<NetPBMFile>, PbmError> {
+    let (header, data_start) = read_header_at(file, 0)?;
+    let is_binary = match header.format {
+        Format::P1 => false,
+        Format::P4 => true,
+        _ => return Err(PbmError::InvalidHeader { offset: 0 }),
+    };
+    let Header { width, height, .. } = header;
+    let data = &file[data_start..];
+
+    let pixels = if is_binary {
+        let (row_bytes, size) = binary_size(width.div_ceil(8), 1, height, 0)?;
+        check_padding(size, height, data.len())?;
+        let mut pixels = binary_rows(data, row_bytes, height)
+            .map(|row| {
+                (0..width.min(row.len() * 8))
+                    .map(|i| row[i / 8] & (0b10000000 >> (i % 8)) != 0)
+                    .collect()
+            })
+            .collect();
+        pad_pixels(&mut pixels, width, height, false);
+        pixels
+    } else {
+        let count = width
+            .checked_mul(height)
+            .ok_or(PbmError::InvalidHeader { offset: 0 })?;
+        check_padding(count, height, data.len())?;
+        let mut bits = data.iter().filter_map(|byte| match byte {
+            b'0' => Some(false),
+            b'1' => Some(true),
+            _ => None,
+        });
+        let pixels: Vec<Vec<bool>> = (0..height)
+            .map(|_| bits.by_ref().take(width).collect())
+            .collect();
+
+        if pixels.iter().map(Vec::len).sum::<usize>() != count {
+            return Err(PbmError::UnexpectedEof);
+        }
+        pixels
+    };
+
+    Ok(NetPBM {
+        class: NetPBMFile {
+            width,
+            height,
+            pixels,
+        },
+        source_format: Some(header.format),
+        comment: None,
+    })
+}
+
+/// load a pgm file from a path.
+/// either P2 or P5
+///
+/// panics if the file can't be read or parsed. use try_load_pgm to handle that instead.
+#[cfg(feature = "std")]
+pub fn load_pgm(path: &str) -> NetPBM<NetPGMFile> {
+    try_load_pgm(path).unwrap()
+}
+
+/// load a pgm file from a path, returning an error instead of panicking.
+/// either P2 or P5
+///
+/// - path - where the file is.
+///
+/// returns - the image, or an error with the byte offset where parsing failed.
+#[cfg(feature = "std")]
+pub fn try_load_pgm(path: &str) -> Result<NetPBM<NetPGMFile>, PbmError> {
+    Ok(parse_pgm(
+        &std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?,
+        0,
+        ParseMode::Lenient,
+    )?
+    .0)
+}
+
+/// load a pgm file from a string.
+/// either P2 or P5
+pub fn load_pgm_str(s: &str) -> Result<NetPBM<NetPGMFile>, PbmError> {
+    parse_pgm(s.as_bytes(), 0, ParseMode::Lenient).map(|(image, _)| image)
+}
+
+/// load a pgm file from its bytes.
+/// either P2 or P5
+///
+/// some old encoders write max_val on the same line as the dimensions. this is only accepted in
+/// lenient mode.
+///
+/// - file - contents of the file.
+/// - mode - whether to fix or reject samples larger than max_val and truncated pixel data.
+pub fn load_pgm_bytes(file: &[u8], mode: ParseMode) -> Result<NetPBM<NetPGMFile>, PbmError> {
+    parse_pgm(file, 0, mode).map(|(image, _)| image)
+}
+
+/// load every image in a file of pgm images written one after another.
+/// each either P2 or P5
+///
+/// - path - where the file is.
+///
+/// returns - the images in the order they appear, or an error if any of them is invalid.
+#[cfg(feature = "std")]
+pub fn load_pgm_all(path: &str) -> Result<Vec<NetPBM<NetPGMFile>>, PbmError> {
+    let file = std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?;
+    let mut images = Vec::new();
+    let mut position = 0;
+    loop {
+        let (image, end) = parse_pgm(&file, position, ParseMode::Lenient)?;
+        images.push(image);
+        position = end;
+        while file.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        if position == file.len() {
+            return Ok(images);
+        }
+    }
+}
+
+/// parse the bytes of a pgm file.
+///
+/// - file  - contents of the whole file.
+/// - start - position of the magic number in file.
+/// - mode  - whether to fix or reject samples larger than max_val and truncated pixel data.
+///
+/// returns - the image, and the position in file right after its pixels.
+fn parse_pgm(
+    file: &[u8],
+    start: usize,
+    mode: ParseMode,
+) -> Result<(NetPBM<NetPGMFile>, usize), PbmError> {
+    let (header, data_start, shared_max_val) = read_header_layout(file, start)?;
+    let (Format::P2 | Format::P5, Some(max_val)) = (header.format, header.max_val) else {
+        return Err(PbmError::InvalidHeader { offset: start });
+    };
+    if let (ParseMode::Strict, Some(offset)) = (mode, shared_max_val) {
+        return Err(PbmError::InvalidHeader { offset });
+    }
+    let is_binary = header.format.is_binary();
+    let Header { width, height, .. } = header;
+    let data = &file[data_start..];
+
+    let (pixels, end) = if is_binary {
+        let sample_bytes = bytes_per_sample(max_val);
+        let (row_bytes, size) = binary_size(width, sample_bytes, height, start)?;
+        if mode == ParseMode::Strict && data.len() < size {
+            return Err(PbmError::UnexpectedEof);
+        }
+        check_padding(size, height, data.len())?;
+        let mut pixels = binary_rows(data, row_bytes, height)
+            .map(|row| {
+                row.chunks_exact(sample_bytes)
+                    .map(|sample| fit_sample(read_sample(sample), max_val, mode))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        pad_pixels(&mut pixels, width, height, 0);
+        (pixels, data_start + data.len().min(size))
+    } else {
+        let (pixels, end) =
+            parse_ascii_samples::<1>(data, data_start, width, height, max_val, mode)?;
+        let pixels = pixels
+            .into_iter()
+            .map(|row| row.into_iter().map(|[value]| value).collect())
+            .collect();
+        (pixels, end)
+    };
+
+    Ok((
+        NetPBM {
+            class: NetPGMFile {
+                width,
+                height,
+                max_val,
+                pixels,
+            },
+            source_format: Some(header.format),
+            comment: None,
+        },
+        end,
+    ))
+}
+
+/// load a ppm file from a path.
+/// either P3 or P6
+///
+/// panics if the file can't be read or parsed. use try_load_ppm to handle that instead.
+#[cfg(feature = "std")]
+pub fn load_ppm(path: &str) -> NetPBM<NetPPMFile> {
+    try_load_ppm(path).unwrap()
+}
+
+/// load a ppm file from a path, returning an error instead of panicking.
+/// either P3 or P6
+///
+/// - path - where the file is.
+///
+/// returns - the image, or an error with the byte offset where parsing failed.
+#[cfg(feature = "std")]
+pub fn try_load_ppm(path: &str) -> Result<NetPBM<NetPPMFile>, PbmError> {
+    Ok(parse_ppm(
+        &std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?,
+        0,
+        ParseMode::Lenient,
+    )?
+    .0)
+}
+
+/// load a ppm file from a string.
+/// either P3 or P6
+pub fn load_ppm_str(s: &str) -> Result<NetPBM<NetPPMFile>, PbmError> {
+    parse_ppm(s.as_bytes(), 0, ParseMode::Lenient).map(|(image, _)| image)
+}
+
+/// load a ppm file from its bytes.
+/// either P3 or P6
+///
+/// - file - contents of the file.
+/// - mode - whether to fix or reject samples larger than max_val and truncated pixel data.
+pub fn load_ppm_bytes(file: &[u8], mode: ParseMode) -> Result<NetPBM<NetPPMFile>, PbmError> {
+    parse_ppm(file, 0, mode).map(|(image, _)| image)
+}
+
+/// load every image in a file of ppm images written one after another.
+/// each either P3 or P6
+///
+/// - path - where the file is.
+///
+/// returns - the images in the order they appear, or an error if any of them is invalid.
+#[cfg(feature = "std")]
+pub fn load_ppm_all(path: &str) -> Result<Vec<NetPBM<NetPPMFile>>, PbmError> {
+    let file = std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?;
+    let mut images = Vec::new();
+    let mut position = 0;
+    loop {
+        let (image, end) = parse_ppm(&file, position, ParseMode::Lenient)?;
+        images.push(image);
+        position = end;
+        while file.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        if position == file.len() {
+            return Ok(images);
+        }
+    }
+}
+
+/// bytes of a reader, stopping at the first error and keeping its kind.
+#[cfg(feature = "std")]
+struct ReaderBytes<R: std::io::Read> {
+    bytes: std::io::Bytes<std::io::BufReader<R>>,
+    error: std::sync::Arc<std::sync::OnceLock<std::io::ErrorKind>>,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Iterator for ReaderBytes<R> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        match self.bytes.next()? {
+            Ok(byte) => Some(byte),
+            Err(e) => {
+                let _ = self.error.set(e.kind());
+                None
+            }
+        }
+    }
+}
+
+/// reads a binary ppm file one row at a time, so the whole image never has to be in memory.
+/// P6
+#[cfg(feature = "std")]
+pub struct PpmRowReader<R: std::io::Read> {
+    bytes: HeaderReader<ReaderBytes<R>>,
+    /// the error that ended bytes early, if any.
+    error: std::sync::Arc<std::sync::OnceLock<std::io::ErrorKind>>,
+    width: usize,
+    height: usize,
+    max_val: u16,
+    row: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> PpmRowReader<R> {
+    /// read the header of a ppm file.
+    ///
+    /// - reader - where to read the file from. it is buffered, so it may be read past the image.
+    ///
+    /// returns - a reader positioned at the first row, or an error if reading fails or the header
+    /// is invalid or not P6.
+    pub fn new(reader: R) -> Result<Self, PbmError> {
+        use std::io::Read;
+
+        let error = std::sync::Arc::new(std::sync::OnceLock::new());
+        let mut bytes = HeaderReader::new(ReaderBytes {
+            bytes: std::io::BufReader::new(reader).bytes(),
+            error: error.clone(),
+        });
+        let header = parse_header(&mut bytes).map_err(|e| read_error(&error).unwrap_or(e))?;
+        let (Format::P6, Some(max_val)) = (header.format, header.max_val) else {
+            return Err(PbmError::InvalidHeader { offset: 0 });
+        };
+
+        Ok(Self {
+            bytes,
+            error,
+            width: header.width,
+            height: header.height,
+            max_val,
+            row: 0,
+        })
+    }
+
+    /// get the width of the image.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// get the height of the image.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// get the maximum value of a channel.
+    pub fn max_val(&self) -> u16 {
+        self.max_val
+    }
+
+    /// read the next row of pixels. samples larger than max_val are clamped.
+    ///
+    /// returns - the colors of the row, left to right, None after the last row, or an error if
+    /// reading fails or the data ends early.
+    pub fn next_row(&mut self) -> Result<Option<Vec<[u16; 3]>>, PbmError> {
+        if self.row == self.height {
+            return Ok(None);
+        }
+
+        let sample_bytes = bytes_per_sample(self.max_val);
+        let mut sample = [0; 2];
+        let mut row = Vec::with_capacity(self.width);
+        for _ in 0..self.width {
+            let mut color = [0; 3];
+            for channel in &mut color {
+                for byte in &mut sample[..sample_bytes] {
+                    *byte = self.bytes.next_byte().ok_or_else(|| {
+                        read_error(&self.error).unwrap_or(PbmError::UnexpectedEof)
+                    })?;
+                }
+                let value = read_sample(&sample[..sample_bytes]);
+                *channel = fit_sample(value, self.max_val, ParseMode::Lenient)?;
+            }
+            row.push(color);
+        }
+        self.row += 1;
+        Ok(Some(row))
+    }
+}
+
+/// get the error that stopped a reader early.
+///
+/// returns - the error as PbmError::Io, or None if the reader just ran out of bytes.
+#[cfg(feature = "std")]
+fn read_error(error: &std::sync::OnceLock<std::io::ErrorKind>) -> Option<PbmError> {
+    error.get().map(|&kind| PbmError::Io { kind })
+}
+
+/// writes a binary ppm file one row at a time, so the whole image never has to be in memory.
+/// P6
+#[cfg(feature = "std")]
+pub struct PpmRowWriter<W: std::io::Write> {
+    writer: W,
+    width: usize,
+    height: usize,
+    max_val: u16,
+    row: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> PpmRowWriter<W> {
+    /// write the header of a ppm file.
+    ///
+    /// - writer  - where to write the file. wrap files in a BufWriter.
+    /// - width   - immutable size for image width.
+    /// - height  - immutable size for image height.
+    /// - max_val - maximum value of a channel.
+    pub fn new(mut writer: W, width: usize, height: usize, max_val: u16) -> Result<Self, PbmError> {
+        writer
+            .write_all(format!("P6\n{} {}\n{}\n", width, height, max_val).as_bytes())
+            .map_err(|e| PbmError::Io { kind: e.kind() })?;
+        Ok(Self {
+            writer,
+            width,
+            height,
+            max_val,
+            row: 0,
+        })
+    }
+
+    /// write the next row of pixels.
+    ///
+    /// - row - colors of the pixels, left to right. must be as long as the image is wide.
+    pub fn write_row(&mut self, row: &[[u16; 3]]) -> Result<(), PbmError> {
+        if self.row == self.height {
+            return Err(PbmError::TooManyPixels);
+        }
+        if row.len() != self.width {
+            return Err(PbmError::RowLengthMismatch {
+                row: self.row,
+                expected: self.width,
+                found: row.len(),
+            });
+        }
+
+        let mut buf = Vec::with_capacity(self.width * 3 * bytes_per_sample(self.max_val));
+        for &sample in row.iter().flatten() {
+            check_sample(sample, self.max_val)?;
+            write_sample(&mut buf, sample, self.max_val, ByteOrder::BigEndian);
+        }
+        self.writer
+            .write_all(&buf)
+            .map_err(|e| PbmError::Io { kind: e.kind() })?;
+        self.row += 1;
+        Ok(())
+    }
+
+    /// flush the file, after checking that every row was written.
+    ///
+    /// returns - the writer, or an error if fewer rows than the height were written.
+    pub fn finish(mut self) -> Result<W, PbmError> {
+        if self.row != self.height {
+            return Err(PbmError::DimensionMismatch {
+                expected: (self.width, self.height),
+                found: (self.width, self.row),
+            });
+        }
+        self.writer
+            .flush()
+            .map_err(|e| PbmError::Io { kind: e.kind() })?;
+        Ok(self.writer)
+    }
+}
+
+/// parse the bytes of a ppm file.
+///
+/// - file  - contents of the whole file.
+/// - start - position of the magic number in file.
+/// - mode  - whether to fix or reject samples larger than max_val and truncated pixel data.
+///
+/// returns - the image, and the position in file right after its pixels.
+fn parse_ppm(
+    file: &[u8],
+    start: usize,
+    mode: ParseMode,
+) -> Result<(NetPBM<NetPPMFile>, usize), PbmError> {
+    let (header, data_start) = read_header_at(file, start)?;
+    let (Format::P3 | Format::P6, Some(max_val)) = (header.format, header.max_val) else {
+        return Err(PbmError::InvalidHeader { offset: start });
+    };
+    let is_binary = header.format.is_binary();
+    let Header { width, height, .. } = header;
+    let data = &file[data_start..];
+
+    let (pixels, end) = if is_binary {
+        let sample_bytes = bytes_per_sample(max_val);
+        // rows and pixels are cut on whole samples, so a 16-bit sample never spans two of them.
+        let (row_bytes, size) = binary_size(width, 3 * sample_bytes, height, start)?;
+        if mode == ParseMode::Strict && data.len() < size {
+            return Err(PbmError::UnexpectedEof);
+        }
+        check_padding(size, height, data.len())?;
+        let mut pixels = binary_rows(data, row_bytes, height)
+            .map(|row| {
+                row.chunks_exact(3 * sample_bytes)
+                    .map(|pixel| {
+                        let mut color = [0; 3];
+                        for (channel, sample) in
+                            color.iter_mut().zip(pixel.chunks_exact(sample_bytes))
+                        {
+                            *channel = fit_sample(read_sample(sample), max_val, mode)?;
+                        }
+                        Ok(color)
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        pad_pixels(&mut pixels, width, height, [0; 3]);
+        (pixels, data_start + data.len().min(size))
+    } else {
+        parse_ascii_samples(data, data_start, width, height, max_val, mode)?
+    };
+
+    Ok((
+        NetPBM {
+            class: NetPPMFile {
+                width,
+                height,
+                max_val,
+                pixels,
+            },
+            source_format: Some(header.format),
+            comment: None,
+        },
+        end,
+    ))
+}
+
+/// load a pam file from a path.
+/// must be P7
+///
+/// panics if the file can't be read or parsed. use try_load_pam to handle that instead.
+#[cfg(feature = "std")]
+pub fn load_pam(path: &str) -> NetPAM {
+    try_load_pam(path).unwrap()
+}
+
+/// load a pam file from a path, returning an error instead of panicking.
+/// must be P7
+///
+/// - path - where the file is.
+///
+/// returns - the image, or an error with the byte offset where parsing failed.
+#[cfg(feature = "std")]
+pub fn try_load_pam(path: &str) -> Result<NetPAM, PbmError> {
+    Ok(parse_pam(
+        &std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?,
+        0,
+    )?
+    .0)
+}
+
+/// load every image in a file of pam images written one after another.
+/// must be P7
+///
+/// - path - where the file is.
+///
+/// returns - the images in the order they appear, or an error if any of them is invalid.
+#[cfg(feature = "std")]
+pub fn load_pam_all(path: &str) -> Result<Vec<NetPAM>, PbmError> {
+    let file = std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?;
+    let mut images = Vec::new();
+    let mut position = 0;
+    loop {
+        let (image, end) = parse_pam(&file, position)?;
+        images.push(image);
+        position = end;
+        while file.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        if position == file.len() {
+            return Ok(images);
+        }
+    }
+}
+
+/// save several pam images one after another in one file.
+///
+/// - images - the images to save, in order.
+/// - path   - where to save them.
+#[cfg(feature = "std")]
+pub fn save_all_raw(images: &[NetPAM], path: &str) -> std::io::Result<()> {
+    let raw: Vec<u8> = images.iter().flat_map(NetPAM::to_raw).collect();
+    std::fs::write(path, raw)
+}
+
+/// save several pbm, pgm or ppm images one after another in one file, in binary.
+///
+/// - images - the images to save, in order.
+/// - path   - where to save them.
+#[cfg(feature = "std")]
+pub fn save_all_raw_pnm<Class: NetPBMSaver>(
+    images: &[NetPBM<Class>],
+    path: &str,
+) -> std::io::Result<()> {
+    let raw: Vec<u8> = images.iter().flat_map(NetPBM::to_raw).collect();
+    std::fs::write(path, raw)
+}
+
+/// load an image of any format and save it again in the given encoding.
+/// any of P1 to P7
+///
+/// - input    - where the image is.
+/// - output   - where to save the image.
+/// - encoding - whether to save the ASCII or binary representation. NetPAM files must be binary.
+#[cfg(feature = "std")]
+pub fn transcode(input: &str, output: &str, encoding: Encoding) -> Result<(), PbmError> {
+    let file = std::fs::read(input).map_err(|e| PbmError::Io { kind: e.kind() })?;
+    let (header, _) = read_header_at(&file, 0)?;
+    let saved = match header.format {
+        Format::P1 | Format::P4 => parse_pbm(&file)?.save(output, encoding, None),
+        Format::P2 | Format::P5 => parse_pgm(&file, 0, ParseMode::Lenient)?
+            .0
+            .save(output, encoding, None),
+        Format::P3 | Format::P6 => parse_ppm(&file, 0, ParseMode::Lenient)?
+            .0
+            .save(output, encoding, None),
+        Format::P7 => parse_pam(&file, 0)?.0.save(output, encoding, None),
+    };
+    saved.map_err(|e| PbmError::Io { kind: e.kind() })
+}
+
+/// parse one pam image that starts at start in file.
+///
+/// returns - the image, and the position in file right after its pixels.
+fn parse_pam(file: &[u8], start: usize) -> Result<(NetPAM, usize), PbmError> {
+    let (header, data_start) = read_header_at(file, start)?;
+    let (Format::P7, Some(depth), Some(max_val)) = (header.format, header.depth, header.max_val)
+    else {
+        return Err(PbmError::InvalidHeader { offset: start });
+    };
+
+    let name = header.tuple_type.as_deref().unwrap_or("");
+    let tuple_type =
+        TupleType::from_tuple_type(name, depth).ok_or(PbmError::InvalidHeader { offset: start })?;
+    if tuple_type.get_depth() as usize != depth {
+        return Err(PbmError::DepthMismatch {
+            expected: tuple_type.get_depth() as usize,
+            found: depth,
+        });
+    }
+
+    let sample_bytes = bytes_per_sample(max_val);
+    let pixel_bytes = depth
+        .checked_mul(sample_bytes)
+        .ok_or(PbmError::InvalidHeader { offset: start })?;
+    let (_, size) = binary_size(header.width, pixel_bytes, header.height, start)?;
+    check_padding(size, header.height, file.len().saturating_sub(data_start))?;
+    let data_end = data_start
+        .checked_add(size)
+        .ok_or(PbmError::UnexpectedEof)?;
+    let data = file
+        .get(data_start..data_end)
+        .ok_or(PbmError::UnexpectedEof)?;
+    let sample = |index: usize| {
+        let bytes = &data[index * sample_bytes..(index + 1) * sample_bytes];
+        fit_sample(read_sample(bytes), max_val, ParseMode::Lenient)
+    };
+    let pixels = (0..header.height)
+        .map(|y| {
+            (0..header.width)
+                .map(|x| {
+                    (0..depth)
+                        .map(|c| sample((y * header.width + x) * depth + c))
+                        .collect()
+                })
+                .collect()
+        })
+        .collect::<Result<_, _>>()?;
+    let gamma = header
+        .comments
+        .iter()
+        .find_map(|comment| comment.strip_prefix("GAMMA")?.trim().parse().ok())
+        .filter(|gamma: &f64| gamma.is_finite() && *gamma > 0.0);
+
+    Ok((
+        NetPAM {
+            width: header.width,
+            height: header.height,
+            depth,
+            max_val,
+            tuple_type,
+            pixels,
+            gamma,
+        },
+        data_end,
+    ))
+}
+
+/// load a pfm file from a path.
+/// either Pf or PF
+///
+/// panics if the file can't be read or parsed. use try_load_pfm to handle that instead.
+#[cfg(feature = "std")]
+pub fn load_pfm(path: &str) -> NetPFMFile {
+    try_load_pfm(path).unwrap()
+}
+
+/// load a pfm file from a path, returning an error instead of panicking.
+/// either Pf or PF
+///
+/// - path - where the file is.
+///
+/// returns - the image, or an error with the byte offset where parsing failed.
+#[cfg(feature = "std")]
+pub fn try_load_pfm(path: &str) -> Result<NetPFMFile, PbmError> {
+    load_pfm_bytes(&std::fs::read(path).map_err(|e| PbmError::Io { kind: e.kind() })?)
+}
+
+/// load a pfm file from its bytes.
+/// either Pf or PF
+pub fn load_pfm_bytes(file: &[u8]) -> Result<NetPFMFile, PbmError> {
+    let mut reader = HeaderReader::new(file.iter().copied());
+
+    let depth = match [reader.next_byte(), reader.next_byte()] {
+        [Some(b'P'), Some(b'f')] => 1,
+        [Some(b'P'), Some(b'F')] => 3,
+        _ => return Err(PbmError::InvalidHeader { offset: 0 }),
+    };
+    if !reader
+        .next_byte()
+        .is_some_and(|byte| byte.is_ascii_whitespace())
+    {
+        return Err(PbmError::InvalidHeader { offset: 2 });
+    }
+
+    // pfm has no comments, so the header is read without them.
+    let width: usize = reader.next_plain_number()?;
+    let height: usize = reader.next_plain_number()?;
+    let scale: f32 = reader.next_plain_number()?;
+    // a scale of -0 still marks little endian, so the sign is checked rather than the value.
+    let byte_order = if scale.is_sign_negative() {
+        ByteOrder::LittleEndian
+    } else {
+        ByteOrder::BigEndian
+    };
+
+    let data = &file[reader.offset..];
+    let (row_bytes, size) = binary_size(width, depth * 4, height, 0)?;
+    if data.len() < size {
+        return Err(PbmError::UnexpectedEof);
+    }
+
+    let mut pixels: Vec<Vec<Vec<f32>>> = binary_rows(data, row_bytes, height)
+        .map(|row| {
+            row.chunks_exact(depth * 4)
+                .map(|pixel| {
+                    pixel
+                        .chunks_exact(4)
+                        .map(|sample| {
+                            let bytes = [sample[0], sample[1], sample[2], sample[3]];
+                            match byte_order {
+                                ByteOrder::BigEndian => f32::from_be_bytes(bytes),
+                                ByteOrder::LittleEndian => f32::from_le_bytes(bytes),
+                            }
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    pixels.reverse();
+
+    Ok(NetPFMFile {
+        width,
+        height,
+        depth,
+        scale: scale.abs(),
+        byte_order,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_collects_comments_between_fields() {
+        let (header, data_start) =
+            read_header_at(b"P2 # one\n3 #two\n2\n# three\n255\n1", 0).unwrap();
+        assert_eq!(
+            (header.width, header.height, header.max_val),
+            (3, 2, Some(255))
+        );
+        assert_eq!(header.comments, ["one", "two", "three"]);
+        assert_eq!(data_start, 30);
+    }
+
+    #[test]
+    fn header_skips_crlf_between_fields() {
+        let file = b"P5\r\n2 1\r\n255\r\x07\x08";
+        let (header, data_start) = read_header_at(file, 0).unwrap();
+        assert_eq!(
+            (header.width, header.height, header.max_val),
+            (2, 1, Some(255))
+        );
+        assert_eq!(data_start, file.len() - 2);
+    }
+
+    #[test]
+    fn header_allows_max_val_on_the_dimensions_line() {
+        let image = load_pgm_str("P2 2 1 255\n1 2").unwrap();
+        assert_eq!(image.class.pixels, vec![vec![1, 2]]);
+
+        let file = b"P5\n4 2 255\n\x01\x02\x03\x04\x05\x06\x07\x08";
+        let image = load_pgm_bytes(file, ParseMode::Lenient).unwrap();
+        assert_eq!(image.class.pixels, vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8]]);
+        assert!(matches!(
+            load_pgm_bytes(file, ParseMode::Strict),
+            Err(PbmError::InvalidHeader { offset: 7 })
+        ));
+        for file in [
+            &b"P5\n4 2\n255\n12345678"[..],
+            b"P5 4\n2 # note\n255\n12345678",
+        ] {
+            assert!(load_pgm_bytes(file, ParseMode::Strict).is_ok());
+        }
+        assert!(load_pgm_bytes(b"P5 4 2 # note\n255 12345678", ParseMode::Strict).is_ok());
+    }
+
+    #[test]
+    fn header_rejects_missing_fields() {
+        for file in [&b"P2 2 1"[..], b"P5\n2", b"P5\n", b"P"] {
+            assert!(matches!(
+                read_header_at(file, 0),
+                Err(PbmError::InvalidHeader { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn lf_after_the_last_field_is_pixel_data() {
+        let file = b"P5\n2 1\n255\r\x0a\x05";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let image = load_pgm_bytes(file, mode).unwrap();
+            assert_eq!(image.class.pixels, vec![vec![10, 5]]);
+        }
+    }
+
+    #[test]
+    fn truncated_pixels_are_padded_or_rejected() {
+        let file = b"P5\n2 2\n255\n\x01\x02\x03";
+        assert!(matches!(
+            load_pgm_bytes(file, ParseMode::Strict),
+            Err(PbmError::UnexpectedEof)
+        ));
+        let image = load_pgm_bytes(file, ParseMode::Lenient).unwrap();
+        assert_eq!(image.class.pixels, vec![vec![1, 2], vec![3, 0]]);
+        assert!(image.validate().is_ok());
+    }
+
+    #[test]
+    fn binary_loaders_ignore_trailing_bytes() {
+        let file = b"P5\n2 1\n255\n\x01\x02\n";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let image = load_pgm_bytes(file, mode).unwrap();
+            assert_eq!(image.class.pixels, vec![vec![1, 2]]);
+        }
+    }
+
+    #[test]
+    fn ascii_pbm_starts_each_row_after_exactly_width_pixels() {
+        let expected = vec![vec![false, true, true], vec![true, false, false]];
+        for file in [
+            "P1\n3 2\n0 1 1\n1 0 0\n",
+            "P1\n3 2\n011100",
+            "P1\n3 2\n0 1\n1 1 0\n0\n",
+        ] {
+            assert_eq!(load_pbm_str(file).unwrap().class.pixels, expected);
+        }
+        assert!(matches!(
+            load_pbm_str("P1\n3 2\n0 1 1 1 0"),
+            Err(PbmError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn ascii_ppm_checks_every_channel_against_max_val() {
+        let file = b"P3\n1 1\n100\n50 200 50\n";
+        let image = load_ppm_bytes(file, ParseMode::Lenient).unwrap();
+        assert_eq!(image.class.pixels, vec![vec![[50, 100, 50]]]);
+        assert!(matches!(
+            load_ppm_bytes(file, ParseMode::Strict),
+            Err(PbmError::SampleOutOfRange {
+                value: 200,
+                max_val: 100
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated_16_bit_samples_are_padded_or_rejected() {
+        let file = b"P5\n2 1\n65535\n\x01\x02\x03";
+        assert!(matches!(
+            load_pgm_bytes(file, ParseMode::Strict),
+            Err(PbmError::UnexpectedEof)
+        ));
+        let image = load_pgm_bytes(file, ParseMode::Lenient).unwrap();
+        assert_eq!(image.class.pixels, vec![vec![0x0102, 0]]);
+
+        let file = b"P6\n1 2\n65535\n\x00\x01\x00\x02\x00\x03\x00";
+        let image = load_ppm_bytes(file, ParseMode::Lenient).unwrap();
+        assert_eq!(image.class.pixels, vec![vec![[1, 2, 3]], vec![[0; 3]]]);
+    }
+
+    #[test]
+    fn ascii_loaders_stop_after_enough_samples() {
+        let image = load_pgm_str("P2 2 1 255\n1 2 not a sample").unwrap();
+        assert_eq!(image.class.pixels, vec![vec![1, 2]]);
+
+        // a huge claimed size must fail cleanly instead of reserving memory for it.
+        assert!(matches!(
+            load_pgm_str("P2 1 100000000000000 255\n0"),
+            Err(PbmError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            load_ppm_str("P3 100000000000 100000000000 255\n0"),
+            Err(PbmError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn errors_report_where_the_file_went_wrong() {
+        assert!(matches!(
+            load_pgm_str("P2\n2 1\n255\n1 x"),
+            Err(PbmError::InvalidSample {
+                offset: 13,
+                pixel: 1
+            })
+        ));
+        assert!(matches!(
+            load_pgm_str("P2\n2 z\n255\n1 2"),
+            Err(PbmError::InvalidHeader { offset: 5 })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn load_pam_all_reads_frames_of_different_sizes() {
+        let mut first = NetPAM::new(2, 1, 255, TupleType::RGB);
+        first.pixels[0][1] = vec![1, 2, 3];
+        let mut second = NetPAM::new(1, 3, 65535, TupleType::GrayscaleAlpha);
+        second.pixels[2][0] = vec![1000, 65535];
+        let frames = [first, second];
+
+        let path = std::env::temp_dir().join("libpbm-load-pam-all.pam");
+        let path = path.to_str().unwrap();
+        save_all_raw(&frames, path).unwrap();
+        let loaded = load_pam_all(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap(), frames);
+    }
+
+    #[test]
+    fn magic_is_followed_by_any_single_whitespace_byte() {
+        for file in [&b"P5 2 1\n255\n\x01\x02"[..], b"P5\t2 1\n255\n\x01\x02"] {
+            let image = load_pgm_bytes(file, ParseMode::Strict).unwrap();
+            assert_eq!(image.class.pixels, vec![vec![1, 2]]);
+        }
+        assert!(matches!(
+            load_pgm_bytes(b"P52 1 255\n\x01\x02", ParseMode::Lenient),
+            Err(PbmError::InvalidHeader { offset: 2 })
+        ));
+    }
+
+    #[test]
+    fn pam_rejects_bad_depths_and_oversized_images() {
+        for depth in ["0", "70000"] {
+            let file =
+                format!("P7\nWIDTH 100000\nHEIGHT 100000\nDEPTH {depth}\nMAXVAL 255\nENDHDR\n");
+            assert!(matches!(
+                parse_pam(file.as_bytes(), 0),
+                Err(PbmError::InvalidHeader { .. })
+            ));
+        }
+        let file = b"P7\nWIDTH 0\nHEIGHT 100000000000\nDEPTH 1\nMAXVAL 255\nENDHDR\n";
+        assert!(matches!(parse_pam(file, 0), Err(PbmError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn pfm_round_trips_in_both_byte_orders() {
+        let mut image = NetPFMFile::new(3, 2, true);
+        image.set_pixel(0, 0, vec![0.5, -1.25, 3.0]);
+        image.set_pixel(2, 1, vec![1e-3, 42.0, -0.0]);
+        for byte_order in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+            for scale in [1.0, 0.0] {
+                image.set_byte_order(byte_order);
+                image.set_scale(scale);
+                assert_eq!(load_pfm_bytes(&image.to_raw()).unwrap(), image);
+            }
+        }
+    }
+
+    #[test]
+    fn pfm_headers_have_no_comments() {
+        assert!(matches!(
+            load_pfm_bytes(b"Pf\n# comment\n1 1\n-1\n\0\0\0\0"),
+            Err(PbmError::InvalidHeader { offset: 3 })
+        ));
+        assert!(load_pfm_bytes(b"Pf\n1 1\n-1\n\0\0\0\0").is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn draw_line_aa_clips_long_lines_and_skips_bad_coordinates() {
+        let mut image = NetPBM::new_pgm(4, 3, 255);
+        image.draw_line_aa(-1.0, 1.0, 1e15, 1.0, 255);
+        assert_eq!(image.class.pixels[1], vec![255; 4]);
+
+        let mut image = NetPBM::new_pgm(4, 3, 255);
+        image.draw_line_aa(f64::NAN, 0.0, 2.0, 2.0, 255);
+        image.draw_line_aa(0.0, 0.0, f64::INFINITY, 0.0, 255);
+        assert_eq!(image.class.pixels, vec![vec![0; 4]; 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn try_load_reports_errors_instead_of_panicking() {
+        let path = std::env::temp_dir().join("libpbm-try-load.pgm");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "P2\n2 1\n255\n1 x").unwrap();
+        let loaded = try_load_pgm(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(
+            loaded,
+            Err(PbmError::InvalidSample {
+                offset: 13,
+                pixel: 1
+            })
+        ));
+        assert!(matches!(
+            try_load_pbm(path),
+            Err(PbmError::Io {
+                kind: std::io::ErrorKind::NotFound
+            })
+        ));
+    }
+
+    #[test]
+    fn custom_tuple_types_are_named_only_in_the_header() {
+        let file =
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE DEPTH_MAP\nENDHDR\n\x01\x02";
+        let (header, _) = read_header_at(file, 0).unwrap();
+        assert_eq!(header.tuple_type.as_deref(), Some("DEPTH_MAP"));
+
+        let (image, _) = parse_pam(file, 0).unwrap();
+        assert_eq!(
+            *image.tuple_type(),
+            TupleType::Custom {
+                depth: 2,
+                tuple_type: ""
+            }
+        );
+        assert_eq!(image.pixels, vec![vec![vec![1, 2]]]);
+    }
+
+    #[test]
+    fn transpose_swaps_axes_of_non_square_images() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (10 * y + x) as u16);
+        let transposed = pgm.transpose();
+        assert_eq!((transposed.class.width, transposed.class.height), (2, 3));
+        assert_eq!(
+            transposed.class.pixels,
+            vec![vec![0, 10], vec![1, 11], vec![2, 12]]
+        );
+        assert_eq!(transposed.transpose().class, pgm.class);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(3, 1, 255, |x, _| [x as u16, 0, 0]);
+        let transposed = ppm.transpose();
+        assert_eq!((transposed.class.width, transposed.class.height), (1, 3));
+        assert_eq!(transposed.class.pixels[2][0], [2, 0, 0]);
+
+        let mut pam = NetPAM::new(3, 2, 255, TupleType::GrayscaleAlpha);
+        pam.set_pixel(2, 0, vec![7, 8]);
+        let transposed = pam.transpose();
+        assert_eq!((transposed.width, transposed.height), (2, 3));
+        assert_eq!(transposed.pixels[2][0], vec![7, 8]);
+        assert_eq!(transposed.transpose(), pam);
+    }
+
+    #[test]
+    fn checkerboard_alternates_squares_including_partial_ones() {
+        let pbm = NetPBM::<NetPBMFile>::checkerboard(5, 3, 2);
+        let (f, t) = (false, true);
+        assert_eq!(
+            pbm.class.pixels,
+            vec![
+                vec![f, f, t, t, f],
+                vec![f, f, t, t, f],
+                vec![t, t, f, f, t]
+            ]
+        );
+
+        let pgm = NetPBM::<NetPGMFile>::checkerboard(5, 3, 100, 2, 10, 200);
+        assert_eq!(pgm.class.pixels[0], vec![10, 10, 100, 100, 10]);
+        assert_eq!(pgm.class.pixels[2], vec![100, 100, 10, 10, 100]);
+
+        let ppm = NetPBM::<NetPPMFile>::checkerboard(3, 1, 255, 0, [1, 2, 3], [4, 5, 6]);
+        assert_eq!(ppm.class.pixels[0], vec![[1, 2, 3], [4, 5, 6], [1, 2, 3]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn color_wheel_has_the_expected_size_and_hues() {
+        let mut wheel = color_wheel(5, 255);
+        assert_eq!((wheel.width, wheel.height, wheel.depth), (5, 5, 3));
+        assert_eq!(*wheel.tuple_type(), TupleType::RGB);
+        // the center has full value, and hue 180 from atan2(0, 0).
+        assert_eq!(wheel.get_pixel(2, 2), Some(vec![0, 255, 255]));
+        // straight left of the center is hue 360, which is red.
+        assert_eq!(wheel.get_pixel(0, 2), Some(vec![153, 0, 0]));
+        assert_eq!(hsv_to_rgb(120.0, 1.0, 1.0), [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn with_max_val_rescales_with_rounding() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(4, 1, 255, |x, _| [0, 1, 128, 255][x]);
+        let wide = pgm.with_max_val(65535);
+        assert_eq!(wide.class.max_val, 65535);
+        assert_eq!(wide.class.pixels[0], vec![0, 257, 32896, 65535]);
+        assert_eq!(wide.with_max_val(255).class, pgm.class);
+
+        // 127 / 255 is just under a half and 128 / 255 just over, so they round apart.
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| [127, 128][x]);
+        assert_eq!(pgm.with_max_val(1).class.pixels[0], vec![0, 1]);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [0, 128, 255]);
+        assert_eq!(
+            ppm.with_max_val(65535).class.pixels[0][0],
+            [0, 32896, 65535]
+        );
+    }
+
+    #[test]
+    fn samples_normalized_maps_zero_and_max_val_to_the_unit_range() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 1, 1000, |x, _| [0, 500, 1000][x]);
+        assert_eq!(pgm.samples_normalized(), vec![0.0, 0.5, 1.0]);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [0, 255, 0]);
+        assert_eq!(ppm.samples_normalized(), vec![0.0, 1.0, 0.0]);
+
+        let empty = NetPBM::new_pgm(2, 1, 0);
+        assert_eq!(empty.samples_normalized(), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn extract_patch_crops_and_rejects_rectangles_outside_the_image() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(4, 3, 255, |x, y| (10 * y + x) as u16);
+        let patch = pgm.extract_patch(1, 1, 2, 2, 2, 2).unwrap();
+        assert_eq!(patch.class.pixels, vec![vec![11, 12], vec![21, 22]]);
+        let stretched = pgm.extract_patch(0, 0, 1, 1, 3, 2).unwrap();
+        assert_eq!(stretched.class.pixels, vec![vec![0; 3]; 2]);
+        assert!(matches!(
+            pgm.extract_patch(3, 0, 2, 1, 1, 1),
+            Err(PbmError::OutOfBounds { x: 3, .. })
+        ));
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, y| [x as u16, y as u16, 9]);
+        let patch = ppm.extract_patch(1, 0, 1, 2, 1, 2).unwrap();
+        assert_eq!(patch.class.pixels, vec![vec![[1, 0, 9]], vec![[1, 1, 9]]]);
+        assert!(ppm.extract_patch(0, 0, 0, 1, 1, 1).is_err());
+    }
+
+    #[test]
+    fn loaded_images_remember_whether_they_were_binary() {
+        let ascii = load_pbm_str("P1\n2 1\n1 0\n").unwrap();
+        assert_eq!(ascii.source_format(), Some(Format::P1));
+        assert!(!ascii.source_format().unwrap().is_binary());
+
+        let raw = parse_pbm(b"P4\n2 1\n\x80").unwrap();
+        assert_eq!(raw.source_format(), Some(Format::P4));
+        assert!(raw.source_format().unwrap().is_binary());
+        assert_eq!(raw.class.pixels, vec![vec![true, false]]);
+
+        assert_eq!(NetPBM::new_pbm(1, 1).source_format(), None);
+    }
+
+    #[test]
+    fn pam_exposes_its_tuple_type_and_depth() {
+        let pam = NetPAM::new(1, 1, 255, TupleType::GrayscaleAlpha);
+        assert_eq!(*pam.tuple_type(), TupleType::GrayscaleAlpha);
+        assert_eq!(pam.depth(), 2);
+
+        let (loaded, _) =
+            parse_pam(&NetPAM::new(2, 1, 255, TupleType::RGBAlpha).to_raw(), 0).unwrap();
+        assert_eq!(*loaded.tuple_type(), TupleType::RGBAlpha);
+    }
+
+    #[test]
+    fn tuple_types_compare_by_variant_and_custom_fields() {
+        assert_eq!(TupleType::RGB, TupleType::RGB);
+        assert_ne!(TupleType::RGB, TupleType::RGBAlpha);
+
+        let custom = |depth, tuple_type| TupleType::Custom { depth, tuple_type };
+        assert_eq!(custom(2, "UV"), custom(2, "UV"));
+        assert_ne!(custom(2, "UV"), custom(3, "UV"));
+        assert_ne!(custom(2, "UV"), custom(2, "XY"));
+        assert_ne!(custom(1, "GRAYSCALE"), TupleType::Grayscale);
+    }
+
+    #[test]
+    fn into_tiles_leaves_smaller_tiles_at_the_edges() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(5, 3, 255, |x, y| [x as u16, y as u16, 0]);
+        let tiles = ppm.into_tiles(2, 2);
+        let sizes: Vec<Vec<(usize, usize)>> = tiles
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|t| (t.class.width, t.class.height))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![vec![(2, 2), (2, 2), (1, 2)], vec![(2, 1), (2, 1), (1, 1)]]
+        );
+        assert_eq!(tiles[1][2].class.pixels, vec![vec![[4, 2, 0]]]);
+        assert!(ppm.into_tiles(0, 2).is_empty());
+    }
+
+    #[test]
+    fn from_tiles_reassembles_split_images_and_rejects_ragged_tiles() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(5, 3, 255, |x, y| [x as u16, y as u16, 7]);
+        let mut tiles = ppm.into_tiles(2, 2);
+        assert_eq!(NetPBM::from_tiles(&tiles).unwrap().class, ppm.class);
+
+        tiles[1].pop();
+        assert!(matches!(
+            NetPBM::from_tiles(&tiles),
+            Err(PbmError::TileCountMismatch {
+                row: 1,
+                expected: 3,
+                found: 2
+            })
+        ));
+        tiles[1].push(NetPBM::new_ppm(2, 1, 255));
+        assert!(matches!(
+            NetPBM::from_tiles(&tiles),
+            Err(PbmError::DimensionMismatch {
+                expected: (1, 1),
+                found: (2, 1)
+            })
+        ));
+        assert!(matches!(NetPBM::from_tiles(&[]), Err(PbmError::NoTiles)));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn par_map_pixels_matches_map_pixels() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(37, 23, 1000, |x, y| (x * y) as u16);
+        let (mut serial, mut parallel) = (pgm.clone(), pgm);
+        serial.map_pixels(|v| v * 3 + 1);
+        parallel.par_map_pixels(|v| v * 3 + 1);
+        assert_eq!(parallel.class, serial.class);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(19, 31, 255, |x, y| [x as u16, y as u16, 200]);
+        let (mut serial, mut parallel) = (ppm.clone(), ppm);
+        serial.map_pixels(|[r, g, b]| [g, b + 100, r]);
+        parallel.par_map_pixels(|[r, g, b]| [g, b + 100, r]);
+        assert_eq!(parallel.class, serial.class);
+    }
+
+    #[test]
+    fn size_estimates_match_the_encoded_lengths() {
+        let pbm = NetPBM::<NetPBMFile>::checkerboard(11, 3, 2);
+        let pgm = NetPBM::<NetPGMFile>::from_fn(5, 4, 1000, |x, y| (x * 200 + y) as u16);
+        let ppm = NetPBM::<NetPPMFile>::from_fn(3, 2, 65535, |x, _| [x as u16 * 30000, 7, 0]);
+        assert_eq!(pbm.raw_size(), pbm.to_raw().len());
+        assert_eq!(pgm.raw_size(), pgm.to_raw().len());
+        assert_eq!(ppm.raw_size(), ppm.to_raw().len());
+        assert!(pbm.ascii_size_estimate() >= pbm.to_ascii(None).len());
+        assert!(pgm.ascii_size_estimate() >= pgm.to_ascii(None).len());
+        assert!(ppm.ascii_size_estimate() >= ppm.to_ascii(None).len());
+
+        let pam = NetPAM::new(3, 2, 300, TupleType::RGBAlpha);
+        assert_eq!(pam.raw_size(), pam.to_raw().len());
+    }
+
+    #[test]
+    fn string_loaders_parse_ascii_images_and_report_errors() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 9, |x, y| [x as u16, y as u16, 9]);
+        assert_eq!(
+            load_ppm_str(&ppm.to_ascii(Some("hi"))).unwrap().class,
+            ppm.class
+        );
+        let pgm = load_pgm_str("P2\n3 1\n15\n0 7 15\n").unwrap();
+        assert_eq!(pgm.class.pixels, vec![vec![0, 7, 15]]);
+        let pbm = load_pbm_str("P1\n1 2\n1\n0\n").unwrap();
+        assert_eq!(pbm.class.pixels, vec![vec![true], vec![false]]);
+
+        assert!(matches!(
+            load_pgm_str("P3\n1 1\n255\n0 0 0\n"),
+            Err(PbmError::InvalidHeader { offset: 0 })
+        ));
+        assert!(matches!(
+            load_ppm_str(""),
+            Err(PbmError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn into_inner_returns_the_wrapped_image() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| x as u16 + 1);
+        let expected = pgm.inner().clone();
+        let inner: NetPGMFile = pgm.into_inner();
+        assert_eq!(inner, expected);
+        assert_eq!(inner.pixels, vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn set_row_replaces_rows_and_rejects_bad_ones() {
+        let mut pbm = NetPBM::new_pbm(3, 2);
+        pbm.set_row(1, &[true, false, true]).unwrap();
+        assert_eq!(pbm.class.pixels[1], vec![true, false, true]);
+        assert!(matches!(
+            pbm.set_row(0, &[true]),
+            Err(PbmError::DimensionMismatch {
+                expected: (3, 1),
+                found: (1, 1)
+            })
+        ));
+        assert!(matches!(
+            pbm.set_row(2, &[true; 3]),
+            Err(PbmError::OutOfBounds { y: 2, .. })
+        ));
+
+        let mut pgm = NetPBM::new_pgm(2, 1, 100);
+        pgm.set_row(0, &[5, 100]).unwrap();
+        assert_eq!(pgm.class.pixels[0], vec![5, 100]);
+        assert!(matches!(
+            pgm.set_row(0, &[5, 101]),
+            Err(PbmError::SampleOutOfRange { value: 101, .. })
+        ));
+
+        let mut ppm = NetPBM::new_ppm(1, 1, 255);
+        ppm.set_row(0, &[[1, 2, 3]]).unwrap();
+        assert!(ppm.set_row(0, &[]).is_err());
+
+        let mut pam = NetPAM::new(1, 1, 255, TupleType::GrayscaleAlpha);
+        pam.set_row(0, &[vec![1, 2]]).unwrap();
+        assert!(matches!(
+            pam.set_row(0, &[vec![1]]),
+            Err(PbmError::DepthMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert_eq!(pam.pixels[0], vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn get_row_returns_rows_and_none_past_the_end() {
+        let pbm = NetPBM::<NetPBMFile>::checkerboard(2, 2, 1);
+        assert_eq!(pbm.get_row(1), Some(&[true, false][..]));
+        assert_eq!(pbm.get_row(2), None);
+
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 3, 255, |x, y| (x + y) as u16);
+        assert_eq!(pgm.get_row(2), Some(&[2, 3][..]));
+        assert_eq!(pgm.get_row(3), None);
+
+        let ppm = NetPBM::new_ppm(1, 1, 255);
+        assert_eq!(ppm.get_row(0), Some(&[[0, 0, 0]][..]));
+        assert_eq!(ppm.get_row(usize::MAX), None);
+
+        let pam = NetPAM::new(1, 1, 255, TupleType::Grayscale);
+        assert_eq!(pam.get_row(0), Some(&[vec![0]][..]));
+        assert_eq!(pam.get_row(1), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn save_writes_the_chosen_encoding() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 2, 255, |x, y| (x * 100 + y) as u16);
+        let path = std::env::temp_dir().join("libpbm-save.pgm");
+        let path = path.to_str().unwrap();
+
+        pgm.save(path, Encoding::Ascii, Some("note")).unwrap();
+        assert_eq!(
+            std::fs::read(path).unwrap(),
+            pgm.to_ascii(Some("note")).into_bytes()
+        );
+        pgm.save(path, Encoding::Binary, Some("ignored")).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), pgm.to_raw());
+        std::fs::remove_file(path).unwrap();
+
+        let pam = NetPAM::new(1, 1, 255, TupleType::RGB);
+        let error = pam.save(path, Encoding::Ascii, None).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        assert!(std::fs::metadata(path).is_err());
+    }
+
+    #[test]
+    fn set_pixel_clamped_saturates_at_max_val() {
+        let mut pgm = NetPBM::new_pgm(2, 1, 100);
+        pgm.set_pixel(0, 0, 500);
+        pgm.set_pixel_clamped(1, 0, 500);
+        assert_eq!(pgm.class.pixels[0], vec![0, 100]);
+
+        let mut ppm = NetPBM::new_ppm(1, 1, 100);
+        ppm.set_pixel_clamped(0, 0, [20, 101, u16::MAX]);
+        assert_eq!(ppm.class.pixels[0][0], [20, 100, 100]);
+        ppm.set_pixel_clamped(1, 0, [1, 1, 1]);
+        assert_eq!(ppm.class.pixels, vec![vec![[20, 100, 100]]]);
+    }
+
+    #[test]
+    fn pixel_writer_fills_in_order_and_stops_at_the_end() {
+        let mut pgm = NetPBM::new_pgm(2, 2, 10);
+        let mut writer = pgm.writer();
+        writer.push(1).unwrap();
+        writer.push(2).unwrap();
+        writer.push(3).unwrap();
+        assert!(matches!(
+            writer.push(11),
+            Err(PbmError::SampleOutOfRange { value: 11, .. })
+        ));
+        assert_eq!(writer.remaining(), 1);
+        writer.push(4).unwrap();
+        assert!(matches!(writer.push(5), Err(PbmError::TooManyPixels)));
+        assert_eq!(pgm.class.pixels, vec![vec![1, 2], vec![3, 4]]);
+
+        let mut pbm = NetPBM::new_pbm(3, 1);
+        let mut writer = pbm.writer();
+        writer.push(true).unwrap();
+        writer.fill_remaining(true).unwrap();
+        assert_eq!(writer.remaining(), 0);
+        assert_eq!(pbm.count_set(), 3);
+
+        let mut ppm = NetPBM::new_ppm(1, 2, 255);
+        let mut writer = ppm.writer();
+        writer.fill_remaining([1, 2, 3]).unwrap();
+        assert!(matches!(writer.push([0; 3]), Err(PbmError::TooManyPixels)));
+        assert_eq!(ppm.class.pixels, vec![vec![[1, 2, 3]]; 2]);
+    }
+
+    #[test]
+    fn flood_fill_stays_inside_its_region() {
+        let mut pbm = NetPBM::<NetPBMFile>::from_fn(3, 2, |x, _| x == 1);
+        pbm.flood_fill(0, 0, true);
+        assert_eq!(pbm.class.pixels, vec![vec![true, true, false]; 2]);
+        // filling with the value already there must not loop forever.
+        pbm.flood_fill(0, 1, true);
+        assert_eq!(pbm.class.pixels, vec![vec![true, true, false]; 2]);
+        pbm.flood_fill(5, 5, false);
+        assert_eq!(pbm.count_set(), 4);
+
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(3, 3, 9, |x, y| if x == y { 9 } else { 0 });
+        pgm.flood_fill(2, 0, 5);
+        assert_eq!(
+            pgm.class.pixels,
+            vec![vec![9, 5, 5], vec![0, 9, 5], vec![0, 0, 9]]
+        );
+        pgm.flood_fill(0, 2, 10);
+        assert_eq!(pgm.class.pixels[2], vec![0, 0, 9]);
+
+        let mut ppm = NetPBM::new_ppm(2, 1, 255);
+        ppm.flood_fill(1, 0, [1, 2, 3]);
+        assert_eq!(ppm.class.pixels, vec![vec![[1, 2, 3]; 2]]);
+
+        let mut pam = NetPAM::new(2, 1, 255, TupleType::GrayscaleAlpha);
+        pam.flood_fill(0, 0, vec![1]);
+        pam.flood_fill(0, 0, vec![1, 256]);
+        assert_eq!(pam.pixels, vec![vec![vec![0, 0]; 2]]);
+        pam.flood_fill(0, 0, vec![1, 255]);
+        assert_eq!(pam.pixels, vec![vec![vec![1, 255]; 2]]);
+    }
+
+    #[test]
+    fn min_max_val_finds_the_largest_sample() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 1, 65535, |x, _| [7, 200, 3][x]);
+        assert_eq!(pgm.min_max_val(), 200);
+        assert_eq!(NetPBM::new_pgm(0, 0, 255).min_max_val(), 0);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 1023, |x, _| [x as u16, 900, 1]);
+        assert_eq!(ppm.min_max_val(), 900);
+    }
+
+    #[test]
+    fn blend_combines_samples_per_mode_and_rejects_mismatches() {
+        let a = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| [200, 50][x]);
+        let b = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |_, _| 100);
+        let blended = |mode| a.blend(&b, mode).unwrap().class.pixels[0].clone();
+        assert_eq!(blended(BlendMode::Add), vec![255, 150]);
+        assert_eq!(blended(BlendMode::Subtract), vec![100, 0]);
+        assert_eq!(blended(BlendMode::Average), vec![150, 75]);
+        assert_eq!(blended(BlendMode::Multiply), vec![78, 19]);
+        assert_eq!(blended(BlendMode::Screen), vec![222, 131]);
+
+        assert!(matches!(
+            a.blend(&NetPBM::new_pgm(1, 2, 255), BlendMode::Add),
+            Err(PbmError::DimensionMismatch {
+                expected: (2, 1),
+                found: (1, 2)
+            })
+        ));
+        assert!(matches!(
+            a.blend(&NetPBM::new_pgm(2, 1, 15), BlendMode::Add),
+            Err(PbmError::MaxValMismatch {
+                expected: 255,
+                found: 15
+            })
+        ));
+
+        let c = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [200, 50, 0]);
+        let d = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [100, 100, 100]);
+        let blended = c.blend(&d, BlendMode::Add).unwrap();
+        assert_eq!(blended.class.pixels[0][0], [255, 150, 100]);
+        assert!(
+            c.blend(&NetPBM::new_ppm(2, 1, 255), BlendMode::Add)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn to_raw_with_writes_16_bit_samples_in_the_chosen_order() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 1, 65535, |x, _| [0x0102, 0xA0B0][x]);
+        let header = b"P5\n2 1\n65535\n".len();
+        let big = pgm.to_raw_with(ByteOrder::BigEndian);
+        assert_eq!(big, pgm.to_raw());
+        assert_eq!(big[header..], [0x01, 0x02, 0xA0, 0xB0]);
+        let little = pgm.to_raw_with(ByteOrder::LittleEndian);
+        assert_eq!(little[header..], [0x02, 0x01, 0xB0, 0xA0]);
+
+        let small = NetPBM::<NetPGMFile>::from_fn(1, 1, 255, |_, _| 7);
+        assert_eq!(small.to_raw_with(ByteOrder::LittleEndian), small.to_raw());
+
+        let mut pam = NetPAM::new(1, 1, 65535, TupleType::Grayscale);
+        pam.set_pixel(0, 0, vec![0x1234]);
+        assert!(
+            pam.to_raw_with(ByteOrder::LittleEndian)
+                .ends_with(&[0x34, 0x12])
+        );
+        assert!(
+            pam.to_raw_with(ByteOrder::BigEndian)
+                .ends_with(&[0x12, 0x34])
+        );
+    }
+
+    #[test]
+    fn split_alpha_separates_planes_and_checks_the_tuple_type() {
+        let mut gray = NetPAM::new(2, 1, 255, TupleType::GrayscaleAlpha);
+        gray.set_pixel(1, 0, vec![10, 20]);
+        let (value, alpha) = gray.split_alpha().unwrap();
+        assert_eq!(value.class.pixels, vec![vec![0, 10]]);
+        assert_eq!(alpha.class.pixels, vec![vec![0, 20]]);
+        assert_eq!(alpha.class.max_val, 255);
+
+        let mut rgba = NetPAM::new(1, 1, 1000, TupleType::RGBAlpha);
+        rgba.set_pixel(0, 0, vec![1, 2, 3, 4]);
+        let (color, alpha) = rgba.split_alpha_rgb().unwrap();
+        assert_eq!(color.class.pixels, vec![vec![[1, 2, 3]]]);
+        assert_eq!(alpha.class.pixels, vec![vec![4]]);
+
+        assert!(matches!(
+            rgba.split_alpha(),
+            Err(PbmError::TupleTypeMismatch {
+                expected: TupleType::GrayscaleAlpha,
+                found: TupleType::RGBAlpha
+            })
+        ));
+        assert!(matches!(
+            gray.split_alpha_rgb(),
+            Err(PbmError::TupleTypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn with_alpha_builds_rgba_and_checks_dimensions() {
+        let color = NetPBM::<NetPPMFile>::from_fn(2, 1, 255, |x, _| [x as u16, 5, 6]);
+        let alpha = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| 100 + x as u16);
+        let pam = with_alpha(&color, &alpha).unwrap();
+        assert_eq!(*pam.tuple_type(), TupleType::RGBAlpha);
+        assert_eq!(
+            pam.pixels,
+            vec![vec![vec![0, 5, 6, 100], vec![1, 5, 6, 101]]]
+        );
+        let (split_color, split_alpha) = pam.split_alpha_rgb().unwrap();
+        assert_eq!(split_color.class, color.class);
+        assert_eq!(split_alpha.class, alpha.class);
+
+        assert!(matches!(
+            with_alpha(&color, &NetPBM::new_pgm(1, 1, 255)),
+            Err(PbmError::DimensionMismatch {
+                expected: (2, 1),
+                found: (1, 1)
+            })
+        ));
+        assert!(with_alpha(&color, &NetPBM::new_pgm(2, 1, 1)).is_err());
+    }
+
+    #[test]
+    fn from_fn_fills_pixels_by_position() {
+        let pbm = NetPBM::<NetPBMFile>::from_fn(3, 2, |x, y| x == y);
+        assert_eq!(
+            pbm.class.pixels,
+            vec![vec![true, false, false], vec![false, true, false]]
+        );
+
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 2, 10, |x, y| (x * 20 + y) as u16);
+        assert_eq!(pgm.class.pixels, vec![vec![0, 10], vec![1, 10]]);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(1, 2, 255, |_, y| [y as u16, 300, 2]);
+        assert_eq!(ppm.class.pixels, vec![vec![[0, 255, 2]], vec![[1, 255, 2]]]);
+    }
+
+    #[test]
+    fn pbm_ascii_output_matches_pbmmake_spacing() {
+        let pbm = NetPBM::<NetPBMFile>::checkerboard(5, 2, 1);
+        assert_eq!(pbm.to_ascii(None), "P1\n5 2\n0 1 0 1 0\n1 0 1 0 1\n");
+        assert_eq!(pbm.to_ascii_wrapped(5), "P1\n5 2\n0 1 0\n1 0\n1 0 1\n0 1\n");
+        assert_eq!(
+            pbm.to_ascii_wrapped(0),
+            "P1\n5 2\n0\n1\n0\n1\n0\n1\n0\n1\n0\n1\n"
+        );
+        assert_eq!(NetPBM::new_pbm(0, 1).to_ascii_wrapped(70), "P1\n0 1\n\n");
+    }
+
+    #[test]
+    fn equalize_spreads_levels_and_leaves_constant_images_alone() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(4, 1, 255, |x, _| [10, 10, 20, 30][x]);
+        pgm.equalize();
+        assert_eq!(pgm.class.pixels[0], vec![0, 0, 128, 255]);
+
+        let mut constant = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |_, _| 42);
+        constant.equalize();
+        assert_eq!(constant.class.pixels, vec![vec![42; 3]; 2]);
+        let mut empty = NetPBM::new_pgm(0, 0, 255);
+        empty.equalize();
+        assert!(empty.class.pixels.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn gaussian_blur_spreads_impulses_and_rejects_bad_sigmas() {
+        let mut pgm =
+            NetPBM::<NetPGMFile>::from_fn(5, 5, 255, |x, y| if (x, y) == (2, 2) { 255 } else { 0 });
+        pgm.gaussian_blur(1.0).unwrap();
+        let center = pgm.class.pixels[2][2];
+        assert!(center < 255 && center > pgm.class.pixels[2][1]);
+        assert!(pgm.class.pixels[2][1] > 0);
+        assert_eq!(pgm.class.pixels[2][1], pgm.class.pixels[1][2]);
+        assert_eq!(pgm.class.pixels[2][1], pgm.class.pixels[2][3]);
+
+        let mut flat = NetPBM::<NetPPMFile>::from_fn(4, 3, 255, |_, _| [10, 20, 30]);
+        flat.gaussian_blur(2.5).unwrap();
+        assert_eq!(flat.class.pixels, vec![vec![[10, 20, 30]; 4]; 3]);
+
+        for sigma in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                pgm.gaussian_blur(sigma),
+                Err(PbmError::InvalidSigma { .. })
+            ));
+            assert!(flat.gaussian_blur(sigma).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn unsharp_mask_steepens_edges() {
+        let step = NetPBM::<NetPGMFile>::from_fn(6, 1, 255, |x, _| if x < 3 { 50 } else { 150 });
+        let mut unchanged = step.clone();
+        unchanged.unsharp_mask(1.0, 0.0).unwrap();
+        assert_eq!(unchanged.class.pixels, step.class.pixels);
+
+        let mut sharpened = step.clone();
+        sharpened.unsharp_mask(1.0, 1.0).unwrap();
+        let row = &sharpened.class.pixels[0];
+        assert!(row[2] < 50 && row[3] > 150);
+        assert!(row[0] <= 50 && row[5] >= 150);
+
+        let mut clamped = step.clone();
+        clamped.unsharp_mask(1.0, 100.0).unwrap();
+        assert_eq!(clamped.class.pixels[0][2], 0);
+        assert_eq!(clamped.class.pixels[0][3], 255);
+
+        assert!(matches!(
+            sharpened.unsharp_mask(0.0, 1.0),
+            Err(PbmError::InvalidSigma { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn rotate_quarter_turns_match_transpose_and_flip() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (y * 3 + x) as u16 * 10);
+        let transposed = pgm.transpose();
+
+        let left = pgm.rotate(90.0, 0);
+        assert_eq!((left.class.width, left.class.height), (2, 3));
+        let mut expected = transposed.class.pixels.clone();
+        expected.reverse();
+        assert_eq!(left.class.pixels, expected);
+
+        let right = pgm.rotate(-90.0, 0);
+        let expected: Vec<Vec<u16>> = transposed
+            .class
+            .pixels
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect();
+        assert_eq!(right.class.pixels, expected);
+
+        assert_eq!(pgm.rotate(0.0, 0).class.pixels, pgm.class.pixels);
+        assert_eq!(pgm.rotate(360.0, 0).class.pixels, pgm.class.pixels);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(4, 4, 255, |_, _| [9, 9, 9]);
+        let tilted = ppm.rotate(45.0, [1, 2, 3]);
+        assert!(tilted.class.width > 4 && tilted.class.height > 4);
+        assert_eq!(tilted.class.pixels[0][0], [1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn shear_shifts_rows_and_columns() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (y * 3 + x) as u16 * 10);
+        assert_eq!(pgm.shear(0.0, 0.0, 99).class.pixels, pgm.class.pixels);
+
+        let horizontal = pgm.shear(1.0, 0.0, 99);
+        assert_eq!((horizontal.class.width, horizontal.class.height), (5, 2));
+        assert_eq!(
+            horizontal.class.pixels,
+            vec![vec![0, 10, 20, 99, 99], vec![99, 30, 40, 50, 99]]
+        );
+
+        let vertical = pgm.shear(0.0, 1.0, 99);
+        assert_eq!((vertical.class.width, vertical.class.height), (3, 5));
+        assert_eq!(vertical.class.pixels[1], vec![30, 10, 99]);
+        assert_eq!(vertical.class.pixels[3], vec![99, 99, 50]);
+
+        let pbm = NetPBM::<NetPBMFile>::from_fn(2, 2, |_, _| true);
+        let sheared = pbm.shear(1.0, 0.0, false);
+        assert_eq!(
+            sheared.class.pixels,
+            vec![
+                vec![true, true, false, false],
+                vec![false, true, true, false]
+            ]
+        );
+        assert!(
+            pbm.shear(1.0, 1.0, false)
+                .class
+                .pixels
+                .iter()
+                .flatten()
+                .all(|x| !x)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn warp_affine_identity_translation_and_singular_matrices() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (y * 3 + x) as u16 * 10);
+        let identity = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(
+            pgm.warp_affine(identity, 3, 2, 7).class.pixels,
+            pgm.class.pixels
+        );
+
+        let shifted = pgm.warp_affine([[1.0, 0.0, 1.0], [0.0, 1.0, 0.0]], 3, 2, 7);
+        assert_eq!(shifted.class.pixels, vec![vec![7, 0, 10], vec![7, 30, 40]]);
+
+        let singular = pgm.warp_affine([[1.0, 1.0, 0.0], [1.0, 1.0, 0.0]], 2, 2, 7);
+        assert_eq!(singular.class.pixels, vec![vec![7; 2]; 2]);
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, y| [x as u16, y as u16, 5]);
+        assert_eq!(
+            ppm.warp_affine(identity, 2, 2, [0; 3]).class.pixels,
+            ppm.class.pixels
+        );
+    }
+
+    #[test]
+    fn validate_reports_corrupted_images() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(2, 2, 100, |_, _| 50);
+        assert!(pgm.validate().is_ok());
+        pgm.class.pixels[1][0] = 101;
+        assert!(matches!(
+            pgm.validate(),
+            Err(PbmError::SampleOutOfRange {
+                value: 101,
+                max_val: 100
+            })
+        ));
+        pgm.class.pixels[1].pop();
+        assert!(matches!(
+            pgm.validate(),
+            Err(PbmError::RowLengthMismatch {
+                row: 1,
+                expected: 2,
+                found: 1
+            })
+        ));
+        pgm.class.pixels.pop();
+        assert!(matches!(
+            pgm.validate(),
+            Err(PbmError::DimensionMismatch { .. })
+        ));
+
+        let mut pam = NetPAM::new(2, 1, 255, TupleType::RGB);
+        assert!(pam.validate().is_ok());
+        pam.pixels[0][1].push(0);
+        assert!(matches!(
+            pam.validate(),
+            Err(PbmError::DepthMismatch {
+                expected: 3,
+                found: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn display_summarizes_each_image_type() {
+        assert_eq!(format!("{}", NetPBM::new_pbm(4, 3)), "PBM 4x3");
+        assert_eq!(
+            format!("{}", NetPBM::new_pgm(5, 2, 255)),
+            "PGM 5x2 maxval=255"
+        );
+        assert_eq!(
+            format!("{}", NetPBM::new_ppm(1, 7, 65535)),
+            "PPM 1x7 maxval=65535"
+        );
+        assert_eq!(format!("[{}]", NetPBM::new_pbm(0, 0)), "[PBM 0x0]");
+    }
+
+    #[test]
+    fn downsample_averages_blocks_and_handles_edge_factors() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(4, 2, 255, |x, y| (y * 4 + x) as u16 * 10);
+        let halved = pgm.downsample(2).unwrap();
+        assert_eq!(halved.class.pixels, vec![vec![25, 45]]);
+
+        let uneven = pgm.downsample(3).unwrap();
+        assert_eq!((uneven.class.width, uneven.class.height), (2, 1));
+        assert_eq!(uneven.class.pixels, vec![vec![30, 50]]);
+
+        let whole = pgm.downsample(10).unwrap();
+        assert_eq!(whole.class.pixels, vec![vec![35]]);
+        assert_eq!(pgm.downsample(1).unwrap().class.pixels, pgm.class.pixels);
+        assert!(matches!(pgm.downsample(0), Err(PbmError::ZeroFactor)));
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, _| [x as u16 * 100, 0, 255]);
+        assert_eq!(
+            ppm.downsample(2).unwrap().class.pixels,
+            vec![vec![[50, 0, 255]]]
+        );
+        assert!(ppm.downsample(0).is_err());
+    }
+
+    #[test]
+    fn mipmap_pyramid_halves_down_to_one_pixel() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(5, 3, 255, |x, y| (x + y) as u16);
+        let levels = pgm.mipmap_pyramid();
+        let sizes: Vec<(usize, usize)> = levels
+            .iter()
+            .map(|level| (level.class.width, level.class.height))
+            .collect();
+        assert_eq!(sizes, vec![(5, 3), (3, 2), (2, 1), (1, 1)]);
+        assert_eq!(levels[0].class.pixels, pgm.class.pixels);
+
+        assert_eq!(NetPBM::new_pgm(1, 1, 255).mipmap_pyramid().len(), 1);
+        let ppm = NetPBM::new_ppm(8, 8, 255);
+        assert_eq!(ppm.mipmap_pyramid().len(), 4);
+    }
+
+    #[test]
+    fn hysteresis_threshold_follows_connected_weak_pixels() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(6, 1, 255, |x, _| [200, 120, 110, 50, 120, 0][x]);
+        let edges = pgm.hysteresis_threshold(100, 150);
+        assert_eq!(
+            edges.class.pixels,
+            vec![vec![true, true, true, false, false, false]]
+        );
+
+        let diagonal =
+            NetPBM::<NetPGMFile>::from_fn(2, 2, 255, |x, y| if x == y { 200 } else { 0 });
+        let edges = diagonal.hysteresis_threshold(100, 150);
+        assert_eq!(
+            edges.class.pixels,
+            vec![vec![true, false], vec![false, true]]
+        );
+
+        // with low above high only strong pixels are set.
+        let edges = pgm.hysteresis_threshold(250, 115);
+        assert_eq!(
+            edges.class.pixels,
+            vec![vec![true, true, false, false, true, false]]
+        );
+    }
+
+    #[test]
+    fn count_set_and_coverage() {
+        let pbm = NetPBM::<NetPBMFile>::from_fn(4, 2, |x, y| x == y || x == 3);
+        assert_eq!(pbm.count_set(), 4);
+        assert_eq!(pbm.coverage(), 0.5);
+        assert_eq!(NetPBM::new_pbm(3, 3).count_set(), 0);
+        assert_eq!(NetPBM::new_pbm(0, 0).coverage(), 0.0);
+    }
+
+    #[test]
+    fn erode_and_dilate_use_a_square() {
+        let block =
+            NetPBM::<NetPBMFile>::from_fn(5, 5, |x, y| (1..4).contains(&x) && (1..4).contains(&y));
+        let eroded = block.erode(1);
+        assert_eq!(eroded.count_set(), 1);
+        assert!(eroded.class.pixels[2][2]);
+        assert_eq!(block.erode(0).class.pixels, block.class.pixels);
+
+        let dot = NetPBM::<NetPBMFile>::from_fn(5, 5, |x, y| (x, y) == (0, 0));
+        let dilated = dot.dilate(1);
+        assert_eq!(dilated.count_set(), 4);
+        assert!(dilated.class.pixels[1][1] && !dilated.class.pixels[2][2]);
+        assert_eq!(dot.dilate(10).count_set(), 25);
+
+        // pixels outside the image count as unset when eroding.
+        let full = NetPBM::<NetPBMFile>::from_fn(3, 3, |_, _| true);
+        assert_eq!(full.erode(1).count_set(), 1);
+    }
+
+    #[test]
+    fn open_removes_specks_and_close_fills_holes() {
+        let specked = NetPBM::<NetPBMFile>::from_fn(7, 7, |x, y| {
+            (x, y) == (0, 6) || ((2..5).contains(&x) && (2..5).contains(&y))
+        });
+        let opened = specked.open(1);
+        assert!(!opened.class.pixels[6][0]);
+        assert_eq!(opened.count_set(), 9);
+
+        let holed = NetPBM::<NetPBMFile>::from_fn(7, 7, |x, y| {
+            (1..6).contains(&x) && (1..6).contains(&y) && (x, y) != (3, 3)
+        });
+        let closed = holed.close(1);
+        assert!(closed.class.pixels[3][3]);
+        assert_eq!(closed.count_set(), 25);
+    }
+
+    #[test]
+    fn distance_transform_measures_chamfer_distances() {
+        let corner = NetPBM::<NetPBMFile>::from_fn(5, 3, |x, y| (x, y) == (0, 0));
+        let distances = corner.distance_transform();
+        assert_eq!(distances.class.max_val, 5);
+        assert_eq!(
+            distances.class.pixels,
+            vec![
+                vec![0, 1, 2, 3, 4],
+                vec![1, 1, 2, 3, 4],
+                vec![2, 2, 3, 4, 5]
+            ]
+        );
+
+        let center = NetPBM::<NetPBMFile>::from_fn(3, 3, |x, y| (x, y) == (1, 1));
+        let distances = center.distance_transform();
+        assert_eq!(distances.class.max_val, 1);
+        assert_eq!(distances.class.pixels[1][1], 0);
+
+        let empty = NetPBM::new_pbm(2, 2).distance_transform();
+        assert_eq!(empty.class.max_val, 65535);
+        assert_eq!(empty.class.pixels, vec![vec![65535; 2]; 2]);
+    }
+
+    #[test]
+    fn carve_seams_narrows_the_image_and_keeps_edges() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(8, 4, 255, |x, _| {
+            if x < 4 { [0, 0, 0] } else { [255, 255, 255] }
+        });
+        let carved = ppm.carve_seams(2);
+        assert_eq!((carved.class.width, carved.class.height), (6, 4));
+        assert!(carved.class.pixels.iter().all(|row| row.len() == 6));
+        // the seams go through the flat areas, so the edge survives.
+        for row in &carved.class.pixels {
+            let edge = row.iter().position(|x| *x == [255, 255, 255]).unwrap();
+            assert!(edge > 0 && row[edge..].iter().all(|x| *x == [255, 255, 255]));
+        }
+        assert_eq!(ppm.carve_seams(0).class.pixels, ppm.class.pixels);
+
+        // removing more seams than there are columns empties the image instead of failing.
+        let emptied = ppm.carve_seams(10);
+        assert_eq!((emptied.class.width, emptied.class.height), (0, 4));
+        assert!(emptied.class.pixels.iter().all(|row| row.is_empty()));
+        assert!(emptied.validate().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn watermark_blends_and_clips_the_logo() {
+        let base = NetPBM::<NetPPMFile>::from_fn(4, 3, 255, |_, _| [100, 100, 100]);
+        let logo = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |_, _| [200, 0, 100]);
+
+        let mut hidden = base.clone();
+        hidden.watermark(&logo, 0, 0, 0.0).unwrap();
+        assert_eq!(hidden.class.pixels, base.class.pixels);
+
+        let mut opaque = base.clone();
+        opaque.watermark(&logo, 1, 1, 1.0).unwrap();
+        assert_eq!(opaque.class.pixels[1][1], [200, 0, 100]);
+        assert_eq!(opaque.class.pixels[2][2], [200, 0, 100]);
+        assert_eq!(opaque.class.pixels[0][0], [100, 100, 100]);
+
+        let mut clipped = base.clone();
+        clipped.watermark(&logo, 3, 2, 0.5).unwrap();
+        assert_eq!(clipped.class.pixels[2][3], [150, 50, 100]);
+        assert_eq!(clipped.class.pixels[1][3], [100, 100, 100]);
+        assert_eq!(clipped.class.pixels[2][2], [100, 100, 100]);
+
+        let mut outside = base.clone();
+        outside.watermark(&logo, 10, 10, 1.0).unwrap();
+        assert_eq!(outside.class.pixels, base.class.pixels);
+
+        let other = NetPBM::new_ppm(1, 1, 15);
+        assert!(matches!(
+            clipped.watermark(&other, 0, 0, 1.0),
+            Err(PbmError::MaxValMismatch {
+                expected: 255,
+                found: 15
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn load_all_reads_mixed_ascii_and_binary_frames() {
+        let path = std::env::temp_dir().join("libpbm-load-all.pnm");
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, b"P2\n2 1\n9\n1 9\n\nP5 1 2 255\n\x07\x08\n").unwrap();
+        let frames = load_pgm_all(path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].class.max_val, 9);
+        assert_eq!(frames[0].class.pixels, vec![vec![1, 9]]);
+        assert_eq!(frames[1].class.pixels, vec![vec![7], vec![8]]);
+
+        std::fs::write(path, b"P6 1 1 255\n\x01\x02\x03P3 1 1 15 4 5 6").unwrap();
+        let frames = load_ppm_all(path).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].class.pixels, vec![vec![[1, 2, 3]]]);
+        assert_eq!(frames[1].class.pixels, vec![vec![[4, 5, 6]]]);
+
+        std::fs::write(path, b"P5 1 1 255\n\x01P9 1 1 255\n\x01").unwrap();
+        let bad_frame = load_pgm_all(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(bad_frame.is_err());
+        assert!(matches!(load_ppm_all(path), Err(PbmError::Io { .. })));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn save_all_raw_pnm_round_trips_through_load_all() {
+        let path = std::env::temp_dir().join("libpbm-save-all.pnm");
+        let path = path.to_str().unwrap();
+
+        let grays = [
+            NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (x + y * 3) as u16),
+            NetPBM::<NetPGMFile>::from_fn(1, 1, 1000, |_, _| 999),
+        ];
+        save_all_raw_pnm(&grays, path).unwrap();
+        let loaded = load_pgm_all(path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        for (loaded, saved) in loaded.iter().zip(&grays) {
+            assert_eq!(loaded.class, saved.class);
+        }
+
+        let colors = [
+            NetPBM::<NetPPMFile>::from_fn(2, 2, 65535, |x, y| [x as u16, y as u16, 60000]),
+            NetPBM::<NetPPMFile>::from_fn(3, 1, 7, |x, _| [x as u16; 3]),
+        ];
+        save_all_raw_pnm(&colors, path).unwrap();
+        let loaded = load_ppm_all(path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        for (loaded, saved) in loaded.iter().zip(&colors) {
+            assert_eq!(loaded.class, saved.class);
+        }
+
+        let frames = [
+            NetPAM::new(1, 2, 1, TupleType::BlackAndWhite),
+            NetPAM::new(2, 1, 255, TupleType::RGBAlpha),
+        ];
+        save_all_raw(&frames, path).unwrap();
+        let loaded = load_pam_all(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap(), frames);
+    }
+
+    #[test]
+    fn row_hashes_find_the_changed_row() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 4, 255, |x, y| (x * 10 + y) as u16);
+        let mut changed = pgm.clone();
+        changed.class.pixels[2][1] += 1;
+        let before = pgm.row_hashes();
+        let after = changed.row_hashes();
+        assert_eq!(before.len(), 4);
+        let differing: Vec<usize> = (0..4).filter(|y| before[*y] != after[*y]).collect();
+        assert_eq!(differing, vec![2]);
+
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |_, _| [1, 2, 3]);
+        let hashes = ppm.row_hashes();
+        assert_eq!(hashes[0], hashes[1]);
+        ppm.class.pixels[0][0][2] = 4;
+        assert_ne!(ppm.row_hashes()[0], hashes[0]);
+
+        let pbm = NetPBM::<NetPBMFile>::from_fn(2, 2, |x, _| x == 0);
+        let mut flipped = pbm.clone();
+        flipped.class.pixels[1][1] = true;
+        assert_eq!(pbm.row_hashes()[0], flipped.row_hashes()[0]);
+        assert_ne!(pbm.row_hashes()[1], flipped.row_hashes()[1]);
+
+        // an empty row hashes to the FNV-1a offset basis.
+        assert_eq!(
+            NetPBM::new_pgm(0, 1, 255).row_hashes(),
+            vec![0xcbf29ce484222325]
+        );
+    }
+
+    #[test]
+    fn crop_to_aspect_keeps_the_center() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(6, 2, 255, |x, y| [x as u16, y as u16, 0]);
+        let square = ppm.crop_to_aspect(1, 1);
+        assert_eq!((square.class.width, square.class.height), (2, 2));
+        assert_eq!(square.class.pixels[0], vec![[2, 0, 0], [3, 0, 0]]);
+
+        let tall = ppm.crop_to_aspect(1, 2);
+        assert_eq!((tall.class.width, tall.class.height), (1, 2));
+        assert_eq!(tall.class.pixels, vec![vec![[2, 0, 0]], vec![[2, 1, 0]]]);
+
+        let wide = NetPBM::<NetPPMFile>::from_fn(4, 5, 255, |x, y| [x as u16, y as u16, 0]);
+        let cropped = wide.crop_to_aspect(2, 1);
+        assert_eq!((cropped.class.width, cropped.class.height), (4, 2));
+        assert_eq!(cropped.class.pixels[0][0], [0, 1, 0]);
+
+        assert_eq!(ppm.crop_to_aspect(3, 1).class.pixels, ppm.class.pixels);
+        assert_eq!(ppm.crop_to_aspect(0, 1).class.pixels, ppm.class.pixels);
+        assert_eq!(ppm.crop_to_aspect(1, 0).class.pixels, ppm.class.pixels);
+    }
+
+    #[test]
+    fn apply_lut_maps_values_and_checks_the_table() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(4, 1, 3, |x, _| x as u16);
+        pgm.apply_lut(&[3, 2, 1, 0]).unwrap();
+        assert_eq!(pgm.class.pixels, vec![vec![3, 2, 1, 0]]);
+
+        assert!(matches!(
+            pgm.apply_lut(&[0, 1, 2]),
+            Err(PbmError::DimensionMismatch {
+                expected: (4, 1),
+                found: (3, 1)
+            })
+        ));
+        assert!(matches!(
+            pgm.apply_lut(&[0, 1, 2, 4]),
+            Err(PbmError::SampleOutOfRange {
+                value: 4,
+                max_val: 3
+            })
+        ));
+        assert_eq!(pgm.class.pixels, vec![vec![3, 2, 1, 0]]);
+
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 1, |x, _| [x as u16; 3]);
+        ppm.apply_lut([&[1, 0], &[0, 1], &[1, 1]]).unwrap();
+        assert_eq!(ppm.class.pixels, vec![vec![[1, 0, 1], [0, 1, 1]]]);
+        assert!(ppm.apply_lut([&[0, 1], &[0], &[0, 1]]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn image_conversions_round_trip() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(3, 2, 255, |x, y| [x as u16 * 100, y as u16, 255]);
+        let rgb = image::RgbImage::from(&ppm);
+        assert_eq!((rgb.width(), rgb.height()), (3, 2));
+        assert_eq!(rgb.get_pixel(2, 1).0, [200, 1, 255]);
+        assert_eq!(NetPBM::<NetPPMFile>::from(&rgb).class, ppm.class);
+
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 3, 255, |x, y| (x * 3 + y) as u16 * 40);
+        let gray = image::GrayImage::from(&pgm);
+        assert_eq!(gray.get_pixel(1, 2).0, [200]);
+        assert_eq!(NetPBM::<NetPGMFile>::from(&gray).class, pgm.class);
+
+        // other max_vals are rescaled to 255.
+        let deep = NetPBM::<NetPGMFile>::from_fn(2, 1, 15, |x, _| x as u16 * 15);
+        let gray = image::GrayImage::from(&deep);
+        assert_eq!(gray.as_raw(), &vec![0, 255]);
+        let back = NetPBM::<NetPGMFile>::from(&gray);
+        assert_eq!(back.class.max_val, 255);
+        assert_eq!(back.class.pixels, vec![vec![0, 255]]);
     }
 
-    fn get_tuple_type(&self) -> &str {
-        match self {
-            TupleType::BlackAndWhite => "BLACKANDWHITE",
-            TupleType::Grayscale => "GRAYSCALE",
-            TupleType::RGB => "RGB",
-            TupleType::BlackAndWhiteAlpha => "BLACKANDWHITE_ALPHA",
-            TupleType::GrayscaleAlpha => "GRAYSCALE_ALPHA",
-            TupleType::RGBAlpha => "RGB_ALPHA",
-            TupleType::Custom { tuple_type, .. } => tuple_type,
-        }
+    #[test]
+    fn write_raw_into_appends_the_raw_bytes() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 2, 1000, |x, y| (x * 500 + y) as u16);
+        let mut buf = b"prefix".to_vec();
+        pgm.write_raw_into(&mut buf);
+        assert_eq!(&buf[..6], b"prefix");
+        assert_eq!(&buf[6..], &pgm.to_raw()[..]);
+
+        let pbm = NetPBM::<NetPBMFile>::from_fn(9, 2, |x, y| x == y);
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 255, |x, _| [x as u16, 1, 2]);
+        let mut buf = Vec::new();
+        pbm.write_raw_into(&mut buf);
+        ppm.write_raw_into(&mut buf);
+        assert_eq!(buf, [pbm.to_raw(), ppm.to_raw()].concat());
+
+        let pam = NetPAM::new(2, 1, 65535, TupleType::GrayscaleAlpha);
+        let mut buf = Vec::new();
+        pam.write_raw_into(&mut buf);
+        assert_eq!(buf, pam.to_raw());
+
+        let pfm = NetPFMFile::new(1, 2, true);
+        let mut buf = Vec::new();
+        pfm.write_raw_into(&mut buf);
+        assert_eq!(buf, pfm.to_raw());
     }
-}
 
-/// type for NetPAM files.
-pub struct NetPAM {
-    width: usize,
-    height: usize,
-    depth: usize,
-    max_val: u16,
-    tuple_type: TupleType,
-    pixels: Vec<Vec<Vec<u16>>>,
-}
+    #[test]
+    #[cfg(feature = "std")]
+    fn linear_accessors_convert_from_and_to_srgb() {
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 255, |x, _| [0, 255, x as u16 * 128]);
+        assert_eq!(ppm.pixel_linear(0, 0), Some([0.0, 1.0, 0.0]));
+        let [_, _, mid] = ppm.pixel_linear(1, 0).unwrap();
+        assert!((mid - 0.2158).abs() < 1e-3);
+        assert_eq!(ppm.pixel_linear(2, 0), None);
+        assert_eq!(ppm.pixel_linear(0, 1), None);
 
-impl NetPAM {
-    /// create a new NetPAM image.
-    pub fn new(width: usize, height: usize, max_val: u16, tuple_type: TupleType) -> Self {
-        let depth = tuple_type.get_depth() as usize;
-        let pixels = vec![vec![vec![0; depth]; width]; height];
+        ppm.set_pixel_linear(0, 0, [0.5, 2.0, -1.0]);
+        assert_eq!(ppm.get_pixel(0, 0), Some([188, 255, 0]));
+        ppm.set_pixel_linear(5, 5, [1.0; 3]);
+        assert_eq!(ppm.class.pixels.len(), 1);
 
-        Self {
-            width,
-            height,
-            depth,
-            max_val,
-            tuple_type,
-            pixels,
+        let mut deep = NetPBM::new_ppm(1, 1, 65535);
+        deep.set_pixel_linear(0, 0, [0.25, 0.5, 0.75]);
+        let back = deep.pixel_linear(0, 0).unwrap();
+        for (back, expected) in back.iter().zip([0.25, 0.5, 0.75]) {
+            assert!((back - expected).abs() < 1e-4);
         }
     }
 
-    /// set a pixels color.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    /// - color - color of pixel. (rgb|v)a order. 0 is black, max_val is white.
-    pub fn set_pixel(&mut self, x: usize, y: usize, color: Vec<u16>) {
-        if x < self.width
-            && y < self.height
-            && color.len() == self.depth
-            && color.iter().all(|x| x <= &self.max_val)
-        {
-            self.pixels[y][x] = color;
+    #[test]
+    fn to_pgm_with_uses_each_coefficient_set() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [100, 200, 50]);
+        let luma = |coeffs| ppm.to_pgm_with(coeffs).unwrap().class.pixels[0][0];
+        assert_eq!(luma(LumaCoeffs::Rec601), 153);
+        assert_eq!(luma(LumaCoeffs::Rec709), 168);
+        assert_eq!(luma(LumaCoeffs::default()), ppm.to_pgm().class.pixels[0][0]);
+        assert_eq!(luma(LumaCoeffs::Custom([0.0, 0.0, 1.0])), 50);
+        assert_eq!(luma(LumaCoeffs::Custom([1.0, 1.0, 1.0])), 255);
+
+        for weights in [[-0.1, 0.5, 0.5], [f64::NAN, 0.0, 0.0]] {
+            assert!(matches!(
+                ppm.to_pgm_with(LumaCoeffs::Custom(weights)),
+                Err(PbmError::InvalidCoefficients { .. })
+            ));
         }
     }
 
-    /// get a pixels color.
-    ///
-    /// - x     - x position of pixel. does nothing if not in image.
-    /// - y     - y position of pixel. does nothing if not in image.
-    ///
-    /// returns - color of pixel. (rgb|v)a order. 0 is black, max_val is white.
-    pub fn get_pixel(&mut self, x: usize, y: usize) -> Option<Vec<u16>> {
-        if x < self.width && y < self.height {
-            return Some(self.pixels[y][x].clone());
-        }
-        None
+    #[test]
+    fn clear_black_and_clear_white_fill_every_pixel() {
+        let mut pbm = NetPBM::<NetPBMFile>::from_fn(3, 2, |x, y| x == y);
+        pbm.clear_black();
+        assert_eq!(pbm.class.pixels, vec![vec![true; 3]; 2]);
+        pbm.clear_white();
+        assert_eq!(pbm.class.pixels, vec![vec![false; 3]; 2]);
+        assert_eq!((pbm.class.width, pbm.class.height), (3, 2));
     }
 
-    /// convert the image to its binary representation.
-    ///
-    /// returns - binary representation of the image.
-    pub fn to_raw(&self) -> Vec<u8> {
-        [
-            format!(
-                "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
-                self.width,
-                self.height,
-                self.depth,
-                self.max_val,
-                self.tuple_type.get_tuple_type(),
-            )
-            .as_bytes(),
-            &self
-                .pixels
-                .iter()
-                .flatten()
-                .flatten()
-                .flat_map(|x| {
-                    if self.max_val > 255 {
-                        vec![(x >> 8) as u8, (x & 0xff) as u8]
-                    } else {
-                        vec![*x as u8]
-                    }
-                })
-                .collect::<Vec<u8>>(),
-        ]
-        .concat()
+    #[test]
+    fn roll_wraps_in_both_directions() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (y * 3 + x) as u16);
+        let rolled = |dx, dy| {
+            let mut image = pgm.clone();
+            image.roll(dx, dy);
+            image.class.pixels
+        };
+        assert_eq!(rolled(1, 0), vec![vec![2, 0, 1], vec![5, 3, 4]]);
+        assert_eq!(rolled(-1, 0), vec![vec![1, 2, 0], vec![4, 5, 3]]);
+        assert_eq!(rolled(0, 1), vec![vec![3, 4, 5], vec![0, 1, 2]]);
+        assert_eq!(rolled(-1, -1), vec![vec![4, 5, 3], vec![1, 2, 0]]);
+        assert_eq!(rolled(7, 4), rolled(1, 0));
+        assert_eq!(rolled(-4, -3), rolled(-1, 1));
+        assert_eq!(rolled(0, 0), pgm.class.pixels);
+
+        let mut empty = NetPBM::new_pbm(0, 0);
+        empty.roll(3, -2);
+        assert!(empty.class.pixels.is_empty());
+
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 255, |x, _| [x as u16; 3]);
+        ppm.roll(isize::MIN, 0);
+        assert_eq!(ppm.class.pixels, vec![vec![[0; 3], [1; 3]]]);
     }
 
-    /// save the image in its binary representation.
-    pub fn save_raw(&self, path: &str) -> std::io::Result<()> {
-        std::fs::write(path, self.to_raw())?;
-        Ok(())
+    #[test]
+    #[cfg(feature = "std")]
+    fn transcode_switches_between_ascii_and_binary() {
+        let dir = std::env::temp_dir();
+        let input = dir.join("libpbm-transcode-in.pnm");
+        let output = dir.join("libpbm-transcode-out.pnm");
+        let (input, output) = (input.to_str().unwrap(), output.to_str().unwrap());
+
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 300, |x, y| (x * 100 + y) as u16);
+        std::fs::write(input, pgm.to_ascii(None)).unwrap();
+        transcode(input, output, Encoding::Binary).unwrap();
+        assert_eq!(std::fs::read(output).unwrap(), pgm.to_raw());
+
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 1, 255, |x, _| [x as u16, 7, 9]);
+        std::fs::write(input, ppm.to_raw()).unwrap();
+        transcode(input, output, Encoding::Ascii).unwrap();
+        assert_eq!(
+            std::fs::read(output).unwrap(),
+            ppm.to_ascii(None).into_bytes()
+        );
+
+        let pbm = NetPBM::<NetPBMFile>::from_fn(9, 1, |x, _| x % 2 == 0);
+        std::fs::write(input, pbm.to_ascii(None)).unwrap();
+        transcode(input, output, Encoding::Binary).unwrap();
+        assert_eq!(std::fs::read(output).unwrap(), pbm.to_raw());
+        std::fs::remove_file(output).unwrap();
+
+        let pam = NetPAM::new(1, 1, 255, TupleType::Grayscale);
+        std::fs::write(input, pam.to_raw()).unwrap();
+        let ascii_pam = transcode(input, output, Encoding::Ascii);
+        std::fs::remove_file(input).unwrap();
+        assert!(matches!(
+            ascii_pam,
+            Err(PbmError::Io {
+                kind: std::io::ErrorKind::Unsupported
+            })
+        ));
+        assert!(matches!(
+            transcode(input, output, Encoding::Binary),
+            Err(PbmError::Io {
+                kind: std::io::ErrorKind::NotFound
+            })
+        ));
     }
-}
 
-/// load a pbm file from a path.  
-/// either P1 or P4
-pub fn load_pbm(path: &str) -> NetPBM<NetPBMFile> {
-    let file = std::fs::read(path).unwrap();
-    let mut file_iter = file.iter();
+    #[test]
+    fn roundtrips_raw_catches_corrupted_images() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 1000, |x, y| (x * 300 + y) as u16);
+        assert!(pgm.roundtrips_raw());
+        pgm.class.pixels[1][2] = 1001;
+        assert!(!pgm.roundtrips_raw());
 
-    let is_binary = file_iter.by_ref().take(2).eq(b"P4");
-    let mut width = None;
-    let mut height = None;
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, _| [x as u16; 3]);
+        assert!(ppm.roundtrips_raw());
+        ppm.class.pixels[0][0] = [256, 0, 0];
+        assert!(!ppm.roundtrips_raw());
 
-    while width.is_none() || height.is_none() {
-        let line: Vec<_> = file_iter.by_ref().take_while(|x| x != &&10).collect();
-        let mut split = line.split(|x| x == &&32);
+        let mut pbm = NetPBM::<NetPBMFile>::from_fn(10, 3, |x, y| (x + y) % 3 == 0);
+        assert!(pbm.roundtrips_raw());
+        pbm.class.pixels[2].push(true);
+        assert!(!pbm.roundtrips_raw());
 
-        if let Some(w) = split.next()
-            && let Ok(w_var) = String::from_utf8(w.iter().copied().copied().collect())
-                .unwrap()
-                .parse::<usize>()
-        {
-            width = Some(w_var);
+        let mut pam = NetPAM::new(2, 1, 65535, TupleType::RGBAlpha);
+        pam.pixels[0][1] = vec![1, 2, 3, 65535];
+        assert!(pam.roundtrips_raw());
+        pam.max_val = 3;
+        assert!(!pam.roundtrips_raw());
+    }
+
+    #[test]
+    fn gamma_comment_round_trips_and_rejects_bad_values() {
+        let mut pam = NetPAM::new(2, 1, 255, TupleType::Grayscale);
+        assert_eq!(pam.gamma(), None);
+        pam.set_gamma(Some(2.2)).unwrap();
+        let raw = pam.to_raw();
+        assert!(raw.windows(11).any(|line| line == b"# GAMMA 2.2"));
+        let (loaded, _) = parse_pam(&raw, 0).unwrap();
+        assert_eq!(loaded.gamma(), Some(2.2));
+        assert_eq!(loaded, pam);
+
+        for gamma in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                pam.set_gamma(Some(gamma)),
+                Err(PbmError::InvalidGamma { .. })
+            ));
         }
+        assert_eq!(pam.gamma(), Some(2.2));
+        pam.set_gamma(None).unwrap();
+        assert!(!pam.to_raw().windows(5).any(|word| word == b"GAMMA"));
 
-        if let Some(h) = split.next()
-            && let Ok(h_var) = String::from_utf8(h.iter().copied().copied().collect())
-                .unwrap()
-                .parse::<usize>()
-        {
-            height = Some(h_var);
+        for gamma in ["inf", "NaN", "-2", "0", "x"] {
+            let file = format!(
+                "P7\nWIDTH 1\nHEIGHT 1\nDEPTH 1\nMAXVAL 255\n# GAMMA {}\nENDHDR\n\x05",
+                gamma
+            );
+            let (loaded, _) = parse_pam(file.as_bytes(), 0).unwrap();
+            assert_eq!(loaded.gamma(), None);
+            assert_eq!(loaded.pixels, vec![vec![vec![5]]]);
         }
     }
 
-    let width = width.unwrap();
-    let height = height.unwrap();
+    #[test]
+    fn with_max_val_leaves_the_original_unchanged() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 1, 255, |x, _| [0, 51, 255][x]);
+        let small = pgm.with_max_val(15);
+        assert_eq!(small.class.max_val, 15);
+        assert_eq!(small.class.pixels, vec![vec![0, 3, 15]]);
+        assert_eq!(pgm.class.max_val, 255);
+        assert_eq!(pgm.class.pixels, vec![vec![0, 51, 255]]);
 
-    let mut pixels = vec![vec![]];
-    let mut num_bits: usize = 0;
+        let mut in_place = pgm.clone();
+        in_place.rescale_max_val(15);
+        assert_eq!(in_place.class, small.class);
 
-    if is_binary {
-        for byte in file_iter {
-            if num_bits > width && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
-            } else {
-                num_bits += 8;
-            }
+        let ppm = NetPBM::<NetPPMFile>::from_fn(1, 1, 15, |_, _| [0, 3, 15]);
+        let deep = ppm.with_max_val(65535);
+        assert_eq!(deep.class.pixels, vec![vec![[0, 13107, 65535]]]);
+        assert_eq!(ppm.class.max_val, 15);
+        assert_eq!(ppm.class.pixels, vec![vec![[0, 3, 15]]]);
+    }
 
-            let len = pixels.len();
+    #[test]
+    fn sixteen_bit_ppm_samples_are_big_endian_across_rows() {
+        let mut file = b"P6 3 2 65535\n".to_vec();
+        let samples: Vec<u16> = (0..18).map(|i| 0x0100 * i + 0xff - i).collect();
+        for sample in &samples {
+            file.extend_from_slice(&[(sample >> 8) as u8, *sample as u8]);
+        }
+        let image = load_ppm_bytes(&file, ParseMode::Strict).unwrap();
+        let expected: Vec<Vec<[u16; 3]>> = samples
+            .chunks(9)
+            .map(|row| row.chunks(3).map(|c| [c[0], c[1], c[2]]).collect())
+            .collect();
+        assert_eq!(image.class.pixels, expected);
+        assert_eq!(image.class.pixels[1][0], [0x09f6, 0x0af5, 0x0bf4]);
 
-            if num_bits - width >= 1 {
-                pixels[len - 1].push(byte & 0b10000000 != 0)
-            }
-            if num_bits - width >= 2 {
-                pixels[len - 1].push(byte & 0b01000000 != 0)
-            }
-            if num_bits - width >= 3 {
-                pixels[len - 1].push(byte & 0b00100000 != 0)
-            }
-            if num_bits - width >= 4 {
-                pixels[len - 1].push(byte & 0b00010000 != 0)
-            }
-            if num_bits - width >= 5 {
-                pixels[len - 1].push(byte & 0b00001000 != 0)
-            }
-            if num_bits - width >= 6 {
-                pixels[len - 1].push(byte & 0b00000100 != 0)
-            }
-            if num_bits - width >= 7 {
-                pixels[len - 1].push(byte & 0b00000010 != 0)
-            }
-            if num_bits - width >= 8 {
-                pixels[len - 1].push(byte & 0b00000001 != 0)
-            }
+        let single = load_ppm_bytes(b"P6 1 1 1000\n\x03\xe8\x00\x01\x02\x00", ParseMode::Strict);
+        assert_eq!(single.unwrap().class.pixels, vec![vec![[1000, 1, 512]]]);
+    }
+
+    #[test]
+    fn fill_gradient_reaches_both_colors() {
+        let (from, to) = ([255, 0, 10], [0, 255, 200]);
+        let mut horizontal = NetPBM::new_ppm(5, 2, 255);
+        horizontal.fill_gradient(from, to, false);
+        for row in &horizontal.class.pixels {
+            assert_eq!(row[0], from);
+            assert_eq!(row[4], to);
+            assert_eq!(row[2], [127, 128, 105]);
         }
-    } else {
-        for byte in file_iter {
-            if num_bits > width && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
+
+        let mut vertical = NetPBM::new_ppm(2, 3, 255);
+        vertical.fill_gradient(from, to, true);
+        assert_eq!(vertical.class.pixels[0], vec![from; 2]);
+        assert_eq!(vertical.class.pixels[2], vec![to; 2]);
+
+        let mut clamped = NetPBM::new_ppm(2, 1, 100);
+        clamped.fill_gradient([500, 0, 0], [0, 0, 1000], false);
+        assert_eq!(clamped.class.pixels, vec![vec![[100, 0, 0], [0, 0, 100]]]);
+
+        let mut single = NetPBM::new_ppm(1, 1, 255);
+        single.fill_gradient(from, to, false);
+        assert_eq!(single.class.pixels, vec![vec![from]]);
+
+        let gray = NetPBM::<NetPGMFile>::gradient(3, 1, 100);
+        assert_eq!(gray.class.pixels, vec![vec![0, 50, 100]]);
+        let color = NetPBM::<NetPPMFile>::gradient(3, 1, 100);
+        assert_eq!(color.class.pixels[0][2], [100; 3]);
+    }
+
+    #[test]
+    fn distinct_levels_counts_each_gray_once() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(4, 2, 65535, |x, y| [0, 7, 7, 65535][x] * y as u16);
+        assert_eq!(pgm.distinct_levels(), 3);
+        assert_eq!(NetPBM::new_pgm(3, 3, 255).distinct_levels(), 1);
+        assert_eq!(NetPBM::new_pgm(0, 0, 255).distinct_levels(), 0);
+        assert_eq!(
+            NetPBM::<NetPGMFile>::gradient(256, 1, 255).distinct_levels(),
+            256
+        );
+    }
+
+    #[test]
+    fn set_pixel_signed_ignores_negative_coordinates() {
+        let mut pgm = NetPBM::new_pgm(2, 2, 255);
+        pgm.set_pixel_signed(-1, 0, 9);
+        pgm.set_pixel_signed(0, -1, 9);
+        pgm.set_pixel_signed(i32::MIN, i32::MIN, 9);
+        pgm.set_pixel_signed(2, 0, 9);
+        assert_eq!(pgm.class.pixels, vec![vec![0; 2]; 2]);
+        pgm.set_pixel_signed(1, 1, 9);
+        assert_eq!(pgm.class.pixels[1][1], 9);
+
+        let mut pbm = NetPBM::new_pbm(1, 1);
+        pbm.set_pixel_signed(-1, -1, true);
+        assert!(!pbm.class.pixels[0][0]);
+        pbm.set_pixel_signed(0, 0, true);
+        assert!(pbm.class.pixels[0][0]);
+
+        let mut ppm = NetPBM::new_ppm(1, 1, 255);
+        ppm.set_pixel_signed(0, -3, [1, 2, 3]);
+        assert_eq!(ppm.class.pixels[0][0], [0; 3]);
+
+        let mut pam = NetPAM::new(1, 1, 255, TupleType::Grayscale);
+        pam.set_pixel_signed(-5, 0, vec![4]);
+        assert_eq!(pam.pixels[0][0], vec![0]);
+        pam.set_pixel_signed(0, 0, vec![4]);
+        assert_eq!(pam.pixels[0][0], vec![4]);
+    }
+
+    #[test]
+    fn to_text_preview_golden_output() {
+        let gradient = NetPBM::<NetPGMFile>::gradient(10, 2, 9);
+        assert_eq!(gradient.to_text_preview(10), "@%#*+=-:. \n");
+        assert_eq!(gradient.to_text_preview(100), "@%#*+=-:. \n");
+        assert_eq!(gradient.to_text_preview(2), "#:\n");
+
+        let split = NetPBM::<NetPGMFile>::from_fn(4, 8, 255, |_, y| if y < 4 { 0 } else { 255 });
+        assert_eq!(split.to_text_preview(2), "@@\n  \n");
+        assert_eq!(split.to_text_preview(4), "@@@@\n@@@@\n    \n    \n");
+        assert_eq!(split.to_text_preview(0), "");
+        assert_eq!(NetPBM::new_pgm(0, 3, 255).to_text_preview(5), "");
+    }
+
+    #[test]
+    fn trim_border_removes_uniform_edges() {
+        let framed = NetPBM::<NetPGMFile>::from_fn(5, 4, 255, |x, y| {
+            if (1..4).contains(&x) && y == 2 {
+                100 + x as u16
             } else {
-                num_bits += 1;
+                3
             }
+        });
+        let trimmed = framed.trim_border(0, 5);
+        assert_eq!((trimmed.class.width, trimmed.class.height), (3, 1));
+        assert_eq!(trimmed.class.pixels, vec![vec![101, 102, 103]]);
+        assert_eq!(framed.trim_border(0, 2).class.pixels, framed.class.pixels);
 
-            let len = pixels.len();
+        let all_border = framed.trim_border(50, 100);
+        assert_eq!((all_border.class.width, all_border.class.height), (0, 0));
+        assert!(all_border.class.pixels.is_empty());
 
-            if byte == &48 {
-                pixels[len - 1].push(false);
-            } else if byte == &49 {
-                pixels[len - 1].push(true);
+        let ppm = NetPBM::<NetPPMFile>::from_fn(3, 3, 255, |x, y| {
+            if (x, y) == (2, 1) {
+                [0, 0, 0]
+            } else {
+                [255, 255, 255]
             }
-        }
+        });
+        let trimmed = ppm.trim_border([255, 255, 255], 0);
+        assert_eq!(trimmed.class.pixels, vec![vec![[0, 0, 0]]]);
+        assert_eq!(ppm.trim_border([255; 3], 255).class.width, 0);
     }
 
-    NetPBM {
-        class: NetPBMFile {
-            width,
-            height,
-            pixels,
-        },
+    #[test]
+    fn try_pack_divides_by_the_common_factor() {
+        let widened = NetPBM::<NetPGMFile>::from_fn(3, 1, 65535, |x, _| [0, 257 * 128, 65535][x]);
+        let packed = widened.try_pack();
+        assert_eq!(packed.class.max_val, 255);
+        assert_eq!(packed.class.pixels, vec![vec![0, 128, 255]]);
+
+        let fives = NetPBM::<NetPGMFile>::from_fn(3, 1, 10, |x, _| x as u16 * 5);
+        let packed = fives.try_pack();
+        assert_eq!(packed.class.max_val, 2);
+        assert_eq!(packed.class.pixels, vec![vec![0, 1, 2]]);
+
+        let unpackable = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| x as u16 * 3 + 1);
+        assert_eq!(unpackable.try_pack().class, unpackable.class);
+        let prime = NetPBM::<NetPGMFile>::from_fn(1, 1, 7, |_, _| 7);
+        assert_eq!(prime.try_pack().class.max_val, 1);
     }
-}
 
-/// load a pgm file from a path.
-/// either P2 or P5
-pub fn load_pgm(path: &str) -> NetPBM<NetPGMFile> {
-    let file = std::fs::read(path).unwrap();
-    let mut file_iter = file.iter();
+    #[test]
+    #[cfg(feature = "std")]
+    fn entropy_measures_bits_per_pixel() {
+        assert_eq!(
+            NetPBM::<NetPGMFile>::from_fn(3, 3, 255, |_, _| 9).entropy(),
+            0.0
+        );
+        assert_eq!(NetPBM::new_pgm(0, 0, 255).entropy(), 0.0);
 
-    let is_binary = file_iter.by_ref().take(2).eq(b"P5");
-    let mut width = None;
-    let mut height = None;
+        let halves = NetPBM::<NetPGMFile>::from_fn(4, 2, 255, |x, _| if x < 2 { 0 } else { 255 });
+        assert!((halves.entropy() - 1.0).abs() < 1e-12);
+        let quarters = NetPBM::<NetPGMFile>::from_fn(4, 1, 255, |x, _| x as u16);
+        assert!((quarters.entropy() - 2.0).abs() < 1e-12);
+    }
 
-    while width.is_none() || height.is_none() {
-        let line: Vec<_> = file_iter.by_ref().take_while(|x| x != &&10).collect();
-        let mut split = line.split(|x| x == &&32);
+    #[test]
+    fn to_rle_writes_alternating_runs() {
+        let pbm = NetPBM::<NetPBMFile>::from_fn(5, 2, |x, y| if y == 0 { x >= 3 } else { x < 1 });
+        let rle = pbm.to_rle();
+        assert_eq!(rle, vec![3, 2, 0, 1, 4]);
+        assert_eq!(NetPBM::from_rle(5, 2, &rle).unwrap().class, pbm.class);
 
-        if let Some(w) = split.next()
-            && let Ok(w_var) = String::from_utf8(w.iter().copied().copied().collect())
-                .unwrap()
-                .parse::<usize>()
-        {
-            width = Some(w_var);
-        }
+        let long = NetPBM::<NetPBMFile>::from_fn(300, 1, |x, _| x >= 200);
+        let rle = long.to_rle();
+        assert_eq!(rle, vec![0xc8, 0x01, 100]);
+        assert_eq!(NetPBM::from_rle(300, 1, &rle).unwrap().class, long.class);
 
-        if let Some(h) = split.next()
-            && let Ok(h_var) = String::from_utf8(h.iter().copied().copied().collect())
-                .unwrap()
-                .parse::<usize>()
-        {
-            height = Some(h_var);
+        assert!(matches!(
+            NetPBM::from_rle(5, 1, &[3]),
+            Err(PbmError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            NetPBM::from_rle(5, 1, &[6]),
+            Err(PbmError::InvalidSample {
+                offset: 0,
+                pixel: 0
+            })
+        ));
+        assert!(matches!(
+            NetPBM::from_rle(2, 1, &[2, 0]),
+            Err(PbmError::TooManyPixels)
+        ));
+        assert!(NetPBM::new_pbm(0, 3).to_rle().is_empty());
+    }
+
+    #[test]
+    fn draw_grid_marks_every_spacing_th_row_and_column() {
+        let mut pbm = NetPBM::new_pbm(5, 4);
+        pbm.draw_grid(2, true);
+        let expected: Vec<Vec<bool>> = (0..4)
+            .map(|y| (0..5).map(|x| x % 2 == 0 || y % 2 == 0).collect())
+            .collect();
+        assert_eq!(pbm.class.pixels, expected);
+
+        let mut pgm = NetPBM::new_pgm(3, 3, 100);
+        pgm.draw_grid(0, 50);
+        pgm.draw_grid(1, 101);
+        assert_eq!(pgm.class.pixels, vec![vec![0; 3]; 3]);
+        pgm.draw_grid(5, 50);
+        assert_eq!(
+            pgm.class.pixels,
+            vec![vec![50, 50, 50], vec![50, 0, 0], vec![50, 0, 0]]
+        );
+
+        let mut ppm = NetPBM::new_ppm(2, 2, 255);
+        ppm.draw_grid(1, [1, 2, 3]);
+        assert_eq!(ppm.class.pixels, vec![vec![[1, 2, 3]; 2]; 2]);
+
+        let mut pam = NetPAM::new(3, 2, 255, TupleType::Grayscale);
+        pam.draw_grid(2, vec![9]);
+        assert_eq!(pam.pixels[0], vec![vec![9]; 3]);
+        assert_eq!(pam.pixels[1], vec![vec![9], vec![0], vec![9]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn downsample_gamma_correct_keeps_checkerboards_bright() {
+        let checkerboard = NetPBM::<NetPPMFile>::from_fn(4, 2, 255, |x, y| {
+            if (x + y) % 2 == 0 { [0; 3] } else { [255; 3] }
+        });
+        let naive = checkerboard.downsample(2).unwrap();
+        let corrected = checkerboard.downsample_gamma_correct(2, 2.2).unwrap();
+        assert_eq!((corrected.class.width, corrected.class.height), (2, 1));
+        assert_eq!(corrected.class.pixels, vec![vec![[186; 3]; 2]]);
+        assert!(corrected.class.pixels[0][0][0] > naive.class.pixels[0][0][0]);
+
+        let linear = checkerboard.downsample_gamma_correct(2, 1.0).unwrap();
+        assert_eq!(linear.class.pixels, naive.class.pixels);
+        let flat = NetPBM::<NetPPMFile>::from_fn(3, 3, 255, |_, _| [10, 100, 200]);
+        let shrunk = flat.downsample_gamma_correct(2, 2.2).unwrap();
+        assert_eq!(shrunk.class.pixels, vec![vec![[10, 100, 200]; 2]; 2]);
+
+        assert!(matches!(
+            checkerboard.downsample_gamma_correct(0, 2.2),
+            Err(PbmError::ZeroFactor)
+        ));
+        for gamma in [0.0, -2.2, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                checkerboard.downsample_gamma_correct(2, gamma),
+                Err(PbmError::InvalidGamma { .. })
+            ));
         }
     }
 
-    let width = width.unwrap();
-    let height = height.unwrap();
+    #[test]
+    fn replace_color_uses_the_tolerance() {
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(3, 1, 255, |x, _| {
+            [[100, 100, 100], [103, 104, 100], [104, 104, 100]][x]
+        });
+        ppm.replace_color([100, 100, 100], [0, 300, 7], 5);
+        assert_eq!(
+            ppm.class.pixels,
+            vec![vec![[0, 255, 7], [0, 255, 7], [104, 104, 100]]]
+        );
+        ppm.replace_color([104, 104, 100], [1, 1, 1], 0);
+        assert_eq!(ppm.class.pixels[0][2], [1, 1, 1]);
 
-    let max_val = String::from_utf8(
-        file_iter
-            .by_ref()
-            .take_while(|x| x != &&10)
-            .copied()
-            .collect(),
-    )
-    .unwrap()
-    .parse()
-    .unwrap();
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(4, 1, 100, |x, _| [10, 13, 7, 14][x]);
+        pgm.replace_color(10, 200, 3);
+        assert_eq!(pgm.class.pixels, vec![vec![100, 100, 100, 14]]);
+    }
 
-    let mut pixels = vec![vec![]];
-    let mut num_bits: usize = 0;
+    #[test]
+    fn difference_image_averages_channel_differences() {
+        let a = NetPBM::<NetPPMFile>::from_fn(3, 1, 255, |_, _| [10, 20, 30]);
+        let b = NetPBM::<NetPPMFile>::from_fn(3, 1, 255, |x, _| {
+            [[10, 20, 30], [13, 20, 30], [255, 255, 255]][x]
+        });
+        let diff = a.difference_image(&b).unwrap();
+        assert_eq!(diff.class.max_val, 255);
+        assert_eq!(diff.class.pixels, vec![vec![0, 1, 235]]);
+        assert_eq!(b.difference_image(&a).unwrap().class, diff.class);
 
-    let mut temp = 0;
+        let black = NetPBM::new_ppm(1, 1, 255);
+        let white = NetPBM::<NetPPMFile>::from_fn(1, 1, 255, |_, _| [255; 3]);
+        assert_eq!(
+            black.difference_image(&white).unwrap().class.pixels,
+            vec![vec![255]]
+        );
 
-    if is_binary {
-        for byte in file_iter {
-            if num_bits > width * if max_val > 255 { 2 } else { 1 } && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
-            } else {
-                num_bits += 1;
-            }
+        assert!(matches!(
+            a.difference_image(&NetPBM::new_ppm(3, 2, 255)),
+            Err(PbmError::DimensionMismatch {
+                expected: (3, 1),
+                found: (3, 2)
+            })
+        ));
+        assert!(matches!(
+            a.difference_image(&NetPBM::new_ppm(3, 1, 15)),
+            Err(PbmError::MaxValMismatch {
+                expected: 255,
+                found: 15
+            })
+        ));
+    }
 
-            let len = pixels.len();
+    #[test]
+    fn stored_comment_is_written_by_to_ascii() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(1, 1, 9, |_, _| 4);
+        assert_eq!(pgm.comment(), None);
+        pgm.set_comment("made by hand");
+        assert_eq!(pgm.comment(), Some("made by hand"));
+        assert_eq!(pgm.to_ascii(None), "P2\n# made by hand\n1 1\n9\n4\n");
+        assert_eq!(pgm.to_ascii(Some("other")), "P2\n# other\n1 1\n9\n4\n");
 
-            if max_val > 255 {
-                if num_bits % 2 == 1 {
-                    temp = (*byte as u16) << 8;
-                } else {
-                    temp += *byte as u16;
-                    pixels[len - 1].push(temp);
-                }
-            } else {
-                pixels[len - 1].push(*byte as u16);
-            }
+        let copy = pgm.clone();
+        assert_eq!(copy.comment(), Some("made by hand"));
+        let loaded = load_pgm_str(&copy.to_ascii(None)).unwrap();
+        assert_eq!(loaded.class, pgm.class);
+
+        let mut pbm = NetPBM::new_pbm(1, 1);
+        pbm.set_comment(String::from("two\nlines"));
+        assert_eq!(pbm.to_ascii(None), "P1\n# two\n# lines\n1 1\n0\n");
+        assert!(!pbm.to_raw().contains(&b'#'));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sample_bilinear_interpolates_inside_the_image() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(2, 2, 255, |x, y| [[0, 100], [200, 255]][y][x]);
+        assert_eq!(pgm.sample_bilinear(0.0, 0.0), Some(0));
+        assert_eq!(pgm.sample_bilinear(1.0, 1.0), Some(255));
+        assert_eq!(pgm.sample_bilinear(0.0, 1.0), Some(200));
+        assert_eq!(pgm.sample_bilinear(0.5, 0.0), Some(50));
+        assert_eq!(pgm.sample_bilinear(0.5, 0.5), Some(139));
+
+        for (x, y) in [(1.01, 0.0), (0.0, -0.1), (2.0, 2.0), (f64::NAN, 0.0)] {
+            assert_eq!(pgm.sample_bilinear(x, y), None);
         }
-    } else {
-        for word in String::from_utf8(file_iter.copied().collect::<Vec<u8>>())
-            .unwrap()
-            .split_whitespace()
-            .collect::<Vec<&str>>()
-        {
-            if num_bits >= width && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
+        assert_eq!(NetPBM::new_pgm(0, 0, 255).sample_bilinear(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn otsu_threshold_splits_a_bimodal_image() {
+        let bimodal = NetPBM::<NetPGMFile>::from_fn(4, 4, 255, |x, y| {
+            if (x + y) % 2 == 0 {
+                40 + x as u16
             } else {
-                num_bits += 1;
+                200 + y as u16
             }
+        });
+        let threshold = bimodal.otsu_threshold();
+        assert!(threshold > 43 && threshold <= 200);
+        let light = bimodal
+            .class
+            .pixels
+            .iter()
+            .flatten()
+            .filter(|p| **p >= threshold);
+        assert_eq!(light.count(), 8);
 
-            let len = pixels.len();
+        let two_levels = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |x, _| [10, 200][x]);
+        assert_eq!(two_levels.otsu_threshold(), 105);
+        assert_eq!(
+            NetPBM::<NetPGMFile>::from_fn(3, 3, 255, |_, _| 9).otsu_threshold(),
+            0
+        );
+        assert_eq!(NetPBM::new_pgm(0, 0, 255).otsu_threshold(), 0);
+    }
 
-            if let Ok(num) = word.parse() {
-                pixels[len - 1].push(num);
-            } else {
-                num_bits -= 1;
-            }
-        }
+    #[test]
+    fn saturating_add_and_sub_stop_at_the_limits() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(3, 1, 100, |x, _| [0, 50, 100][x]);
+        pgm.saturating_add(60);
+        assert_eq!(pgm.class.pixels, vec![vec![60, 100, 100]]);
+        pgm.saturating_sub(70);
+        assert_eq!(pgm.class.pixels, vec![vec![0, 30, 30]]);
+        pgm.saturating_sub(u16::MAX);
+        assert_eq!(pgm.class.pixels, vec![vec![0; 3]]);
+
+        let mut deep = NetPBM::<NetPGMFile>::from_fn(1, 1, 65535, |_, _| 65000);
+        deep.saturating_add(1000);
+        assert_eq!(deep.class.pixels, vec![vec![65535]]);
     }
 
-    NetPBM {
-        class: NetPGMFile {
-            width,
-            height,
-            max_val,
-            pixels,
-        },
+    #[test]
+    fn region_pixels_walks_a_rectangle() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (y * 3 + x) as u16);
+        let region: Vec<_> = pgm.region_pixels(1, 0, 2, 2).unwrap().collect();
+        assert_eq!(region, vec![(1, 0, 1), (2, 0, 2), (1, 1, 4), (2, 1, 5)]);
+        assert_eq!(pgm.region_pixels(3, 2, 0, 0).unwrap().count(), 0);
+        assert_eq!(pgm.region_pixels(0, 0, 3, 2).unwrap().count(), 6);
+
+        assert!(matches!(
+            pgm.region_pixels(2, 0, 2, 1),
+            Err(PbmError::OutOfBounds {
+                x: 2,
+                y: 0,
+                width: 2,
+                height: 1
+            })
+        ));
+        assert!(pgm.region_pixels(0, 1, 1, 2).is_err());
+        assert!(pgm.region_pixels(usize::MAX, 0, 2, 1).is_err());
+
+        let pbm = NetPBM::<NetPBMFile>::from_fn(2, 2, |x, y| x == y);
+        let set: Vec<_> = pbm
+            .region_pixels(0, 0, 2, 2)
+            .unwrap()
+            .filter(|p| p.2)
+            .collect();
+        assert_eq!(set, vec![(0, 0, true), (1, 1, true)]);
     }
-}
 
-/// load a ppm file from a path.
-/// either P3 or P6
-pub fn load_ppm(path: &str) -> NetPBM<NetPPMFile> {
-    let file = std::fs::read(path).unwrap();
-    let mut file_iter = file.iter();
+    #[test]
+    fn content_hash_changes_with_any_pixel() {
+        let pgm = NetPBM::<NetPGMFile>::from_fn(3, 2, 255, |x, y| (x + y) as u16);
+        let mut changed = pgm.clone();
+        assert_eq!(changed.content_hash(), pgm.content_hash());
+        changed.class.pixels[1][2] += 1;
+        assert_ne!(changed.content_hash(), pgm.content_hash());
 
-    let is_binary = file_iter.by_ref().take(2).eq(b"P6");
-    let mut width = None;
-    let mut height = None;
+        let wide = NetPBM::<NetPGMFile>::from_fn(2, 1, 255, |_, _| 0);
+        let tall = NetPBM::<NetPGMFile>::from_fn(1, 2, 255, |_, _| 0);
+        assert_ne!(wide.content_hash(), tall.content_hash());
+        assert_ne!(wide.content_hash(), wide.with_max_val(15).content_hash());
 
-    while width.is_none() || height.is_none() {
-        let line: Vec<_> = file_iter.by_ref().take_while(|x| x != &&10).collect();
-        let mut split = line.split(|x| x == &&32);
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, _| [x as u16; 3]);
+        let mut other = ppm.clone();
+        other.class.pixels[0][0][1] = 9;
+        assert_ne!(ppm.content_hash(), other.content_hash());
 
-        if let Some(w) = split.next()
-            && let Ok(w_var) = String::from_utf8(w.iter().copied().copied().collect())
-            .unwrap()
-            .parse::<usize>()
-        {
-            width = Some(w_var);
-        }
+        let pbm = NetPBM::<NetPBMFile>::from_fn(2, 2, |x, y| x == y);
+        let mut flipped = pbm.clone();
+        assert_eq!(flipped.content_hash(), pbm.content_hash());
+        flipped.class.pixels[0][1] = true;
+        assert_ne!(flipped.content_hash(), pbm.content_hash());
+    }
 
-        if let Some(h) = split.next()
-            && let Ok(h_var) = String::from_utf8(h.iter().copied().copied().collect())
-            .unwrap()
-            .parse::<usize>()
-        {
-            height = Some(h_var);
-        }
+    #[test]
+    fn to_4connected_turns_diagonal_steps_into_staircases() {
+        let diagonal = NetPBM::<NetPBMFile>::from_fn(3, 3, |x, y| x == y);
+        let connected = diagonal.to_4connected();
+        assert_eq!(
+            connected.class.pixels,
+            vec![
+                vec![true, true, false],
+                vec![false, true, true],
+                vec![false, false, true]
+            ]
+        );
+
+        let anti = NetPBM::<NetPBMFile>::from_fn(2, 2, |x, y| x + y == 1);
+        assert_eq!(anti.to_4connected().count_set(), 3);
+
+        let straight = NetPBM::<NetPBMFile>::from_fn(3, 2, |_, y| y == 1);
+        assert_eq!(straight.to_4connected().class, straight.class);
+        assert_eq!(NetPBM::new_pbm(0, 0).to_4connected().class.pixels.len(), 0);
     }
 
-    let width = width.unwrap();
-    let height = height.unwrap();
+    #[test]
+    fn uv_map_encodes_positions() {
+        let uv = NetPBM::<NetPPMFile>::uv_map(4, 2, 255);
+        assert_eq!(uv.class.pixels[0][0], [0, 0, 0]);
+        assert_eq!(uv.class.pixels[0][3], [191, 0, 0]);
+        assert_eq!(uv.class.pixels[1][0], [0, 127, 0]);
+        assert_eq!(uv.class.pixels[1][3], [191, 127, 0]);
 
-    let max_val = String::from_utf8(
-        file_iter
-            .by_ref()
-            .take_while(|x| x != &&10)
-            .copied()
-            .collect(),
-    )
-        .unwrap()
-        .parse()
-        .unwrap();
-
-    let mut pixels = vec![vec![]];
-    let mut num_bits: usize = 0;
-
-    let mut temp = [0; 3];
-    let mut idx = 0;
-
-    if is_binary {
-        for byte in file_iter {
-            if num_bits > width * if max_val > 255 { 6 } else { 3 } && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
-            } else {
-                num_bits += 1;
-            }
+        let deep = NetPBM::<NetPPMFile>::uv_map(2, 2, 65535);
+        assert_eq!(deep.class.pixels[1][1], [32767, 32767, 0]);
+        assert!(
+            NetPBM::<NetPPMFile>::uv_map(0, 0, 255)
+                .class
+                .pixels
+                .is_empty()
+        );
+    }
 
-            let len = pixels.len();
+    #[test]
+    fn clamp_samples_fixes_out_of_range_pixels() {
+        let mut pgm = NetPBM::<NetPGMFile>::from_fn(3, 1, 100, |x, _| [5, 100, 100][x]);
+        pgm.class.pixels[0][2] = 60000;
+        assert!(pgm.validate().is_err());
+        pgm.clamp_samples();
+        assert_eq!(pgm.class.pixels, vec![vec![5, 100, 100]]);
+        assert!(pgm.validate().is_ok());
 
-            if max_val > 255 {
-                if num_bits % 2 == 1 {
-                    temp[idx] = (*byte as u16) << 8;
-                } else {
-                    temp[idx] += *byte as u16;
-                    idx += 1;
-                }
-            } else {
-                temp[idx] = *byte as u16;
-                idx += 1;
-            }
+        let mut ppm = NetPBM::new_ppm(1, 1, 15);
+        ppm.class.pixels[0][0] = [16, 3, 1000];
+        ppm.clamp_samples();
+        assert_eq!(ppm.class.pixels[0][0], [15, 3, 15]);
+        assert!(ppm.roundtrips_raw());
+    }
 
-            if idx == 3 {
-                pixels[len - 1].push(temp);
-                idx = 0;
+    /// a reader that fails once its data runs out.
+    #[cfg(feature = "std")]
+    struct FailingReader<'a>(&'a [u8]);
+
+    #[cfg(feature = "std")]
+    impl std::io::Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(std::io::ErrorKind::ConnectionReset.into());
             }
+            let len = buf.len().min(self.0.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
         }
-    } else {
-        for word in String::from_utf8(file_iter.copied().collect::<Vec<u8>>())
-            .unwrap()
-            .split_whitespace()
-            .collect::<Vec<&str>>()
-        {
-            if num_bits >= width * 3 && pixels.len() < height {
-                pixels.push(vec![]);
-                num_bits = 0;
-            } else {
-                num_bits += 1;
-            }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn ppm_row_reader_decodes_rows_and_reports_read_errors() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(2, 3, 1000, |x, y| [x as u16, y as u16, 999]);
+        let raw = ppm.to_raw();
+        let mut reader = PpmRowReader::new(std::io::Cursor::new(&raw)).unwrap();
+        assert_eq!(
+            (reader.width(), reader.height(), reader.max_val()),
+            (2, 3, 1000)
+        );
+        for row in &ppm.class.pixels {
+            assert_eq!(reader.next_row().unwrap().as_ref(), Some(row));
+        }
+        assert_eq!(reader.next_row().unwrap(), None);
 
-            let len = pixels.len();
+        let mut truncated = PpmRowReader::new(&raw[..raw.len() - 1]).unwrap();
+        truncated.next_row().unwrap();
+        truncated.next_row().unwrap();
+        assert!(matches!(truncated.next_row(), Err(PbmError::UnexpectedEof)));
 
-            if let Ok(num) = word.parse() {
-                temp[idx] = num;
-                idx += 1;
-                if idx == 3 {
-                    pixels[len - 1].push(temp);
-                    idx = 0;
-                }
-            } else {
-                num_bits -= 1;
-            }
+        let mut failing = PpmRowReader::new(FailingReader(&raw[..raw.len() - 15])).unwrap();
+        assert_eq!(
+            failing.next_row().unwrap().as_ref(),
+            Some(&ppm.class.pixels[0])
+        );
+        assert!(matches!(
+            failing.next_row(),
+            Err(PbmError::Io {
+                kind: std::io::ErrorKind::ConnectionReset
+            })
+        ));
+        assert!(matches!(
+            PpmRowReader::new(FailingReader(b"P6 2")),
+            Err(PbmError::Io {
+                kind: std::io::ErrorKind::ConnectionReset
+            })
+        ));
+        assert!(matches!(
+            PpmRowReader::new(&b"P3 1 1 255 1 2 3"[..]),
+            Err(PbmError::InvalidHeader { offset: 0 })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn ppm_row_writer_matches_to_raw_and_checks_the_row_count() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(3, 2, 300, |x, y| [x as u16 * 100, y as u16, 7]);
+        let mut writer = PpmRowWriter::new(Vec::new(), 3, 2, 300).unwrap();
+        for row in &ppm.class.pixels {
+            writer.write_row(row).unwrap();
         }
+        assert!(matches!(
+            writer.write_row(&ppm.class.pixels[0]),
+            Err(PbmError::TooManyPixels)
+        ));
+        assert_eq!(writer.finish().unwrap(), ppm.to_raw());
+
+        let mut short = PpmRowWriter::new(Vec::new(), 3, 2, 300).unwrap();
+        short.write_row(&ppm.class.pixels[0]).unwrap();
+        assert!(matches!(
+            short.write_row(&[[0; 3]; 2]),
+            Err(PbmError::RowLengthMismatch {
+                row: 1,
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            short.write_row(&[[301, 0, 0]; 3]),
+            Err(PbmError::SampleOutOfRange { .. })
+        ));
+        assert!(matches!(
+            short.finish(),
+            Err(PbmError::DimensionMismatch {
+                expected: (3, 2),
+                found: (3, 1)
+            })
+        ));
     }
 
-    NetPBM {
-        class: NetPPMFile {
-            width,
-            height,
-            max_val,
-            pixels,
-        },
+    #[test]
+    fn color_distance_mask_marks_far_pixels() {
+        let ppm = NetPBM::<NetPPMFile>::from_fn(4, 1, 255, |x, _| {
+            [[0, 255, 0], [3, 251, 0], [4, 251, 0], [255, 0, 0]][x]
+        });
+        let mask = ppm.color_distance_mask([0, 255, 0], 5);
+        assert_eq!((mask.class.width, mask.class.height), (4, 1));
+        assert_eq!(mask.class.pixels, vec![vec![false, false, true, true]]);
+        assert_eq!(ppm.color_distance_mask([0, 255, 0], 0).count_set(), 3);
+        assert_eq!(
+            ppm.color_distance_mask([0, 255, 0], u16::MAX).count_set(),
+            0
+        );
     }
-}
 
-/// load a pam file from a path.
-/// must be P7
-pub fn load_pam(path: &str) -> NetPAM {
-    let file = std::fs::read(path).unwrap();
-    let mut file_iter = file.iter();
-
-    let _magic = file_iter.by_ref().take_while(|x| x != &&10);
-    let width = file_iter.by_ref().take_while(|x| x != &&10);
-    let height = file_iter.by_ref().take_while(|x| x != &&10);
-    let depth = file_iter.by_ref().take_while(|x| x != &&10);
-    let max_val = file_iter.by_ref().take_while(|x| x != &&10);
-    let tuple_type = file_iter.by_ref().take_while(|x| x != &&10);
-
-    NetPAM{
-        width: 0,
-        height: 0,
-        depth: 0,
-        max_val: 0,
-        tuple_type: TupleType::BlackAndWhite,
-        pixels: vec![],
-    }
-}
\ No newline at end of file
+    #[test]
+    fn apply_mask_replaces_unmasked_pixels() {
+        let mut ppm = NetPBM::<NetPPMFile>::from_fn(2, 2, 255, |x, y| [x as u16, y as u16, 9]);
+        let mask = NetPBM::<NetPBMFile>::from_fn(2, 2, |x, y| x == y);
+        ppm.apply_mask(&mask, [255, 0, 255]).unwrap();
+        assert_eq!(
+            ppm.class.pixels,
+            vec![
+                vec![[0, 0, 9], [255, 0, 255]],
+                vec![[255, 0, 255], [1, 1, 9]]
+            ]
+        );
+
+        let before = ppm.class.clone();
+        assert!(matches!(
+            ppm.apply_mask(&NetPBM::new_pbm(2, 3), [0; 3]),
+            Err(PbmError::DimensionMismatch {
+                expected: (2, 2),
+                found: (2, 3)
+            })
+        ));
+        assert!(matches!(
+            ppm.apply_mask(&mask, [256, 0, 0]),
+            Err(PbmError::SampleOutOfRange {
+                value: 256,
+                max_val: 255
+            })
+        ));
+        assert_eq!(ppm.class, before);
+
+        let background = NetPBM::<NetPPMFile>::from_fn(3, 1, 255, |x, _| {
+            [[0, 255, 0], [9, 9, 9], [0, 250, 0]][x]
+        });
+        let mut keyed = background.clone();
+        keyed
+            .apply_mask(&background.color_distance_mask([0, 255, 0], 10), [0; 3])
+            .unwrap();
+        assert_eq!(keyed.class.pixels, vec![vec![[0; 3], [9, 9, 9], [0; 3]]]);
+    }
+}